@@ -53,4 +53,18 @@ pub struct Process {
     pub env: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
     #[prost(string, tag = "15")]
     pub service_id: ::prost::alloc::string::String,
+    #[prost(message, repeated, tag = "16")]
+    pub children: ::prost::alloc::vec::Vec<Process>,
+    #[prost(int32, optional, tag = "17")]
+    pub exit_code: ::core::option::Option<i32>,
+    #[prost(string, tag = "18")]
+    pub exit_signal: ::prost::alloc::string::String,
+    #[prost(string, tag = "19")]
+    pub exited_at: ::prost::alloc::string::String,
+    #[prost(uint32, tag = "20")]
+    pub restarts: u32,
+    #[prost(string, tag = "21")]
+    pub health: ::prost::alloc::string::String,
+    #[prost(string, tag = "22")]
+    pub error: ::prost::alloc::string::String,
 }
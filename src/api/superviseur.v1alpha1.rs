@@ -1,10 +1,26 @@
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ServiceOutcome {
+    #[prost(string, tag = "1")]
+    pub service: ::prost::alloc::string::String,
+    /// started, already_running, stopped, not_running or failed
+    #[prost(string, tag = "2")]
+    pub outcome: ::prost::alloc::string::String,
+    #[prost(uint32, tag = "3")]
+    pub pid: u32,
+    #[prost(string, tag = "4")]
+    pub error: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
 pub struct LoadConfigRequest {
     #[prost(string, tag = "1")]
     pub config: ::prost::alloc::string::String,
     #[prost(string, tag = "2")]
     pub file_path: ::prost::alloc::string::String,
+    /// hcl (default), toml, json or yaml
+    #[prost(string, tag = "3")]
+    pub format: ::prost::alloc::string::String,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -25,6 +41,10 @@ pub struct StartRequest {
 pub struct StartResponse {
     #[prost(bool, tag = "1")]
     pub success: bool,
+    #[prost(string, tag = "2")]
+    pub error: ::prost::alloc::string::String,
+    #[prost(message, repeated, tag = "3")]
+    pub outcomes: ::prost::alloc::vec::Vec<ServiceOutcome>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -39,6 +59,10 @@ pub struct StopRequest {
 pub struct StopResponse {
     #[prost(bool, tag = "1")]
     pub success: bool,
+    #[prost(string, tag = "2")]
+    pub error: ::prost::alloc::string::String,
+    #[prost(message, repeated, tag = "3")]
+    pub outcomes: ::prost::alloc::vec::Vec<ServiceOutcome>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -53,6 +77,10 @@ pub struct RestartRequest {
 pub struct RestartResponse {
     #[prost(bool, tag = "1")]
     pub success: bool,
+    #[prost(string, tag = "2")]
+    pub error: ::prost::alloc::string::String,
+    #[prost(message, repeated, tag = "3")]
+    pub outcomes: ::prost::alloc::vec::Vec<ServiceOutcome>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -1029,12 +1057,44 @@ pub struct LogRequest {
     pub config_file_path: ::prost::alloc::string::String,
     #[prost(bool, tag = "3")]
     pub follow: bool,
+    /// stdout (default), stderr or both
+    #[prost(string, tag = "4")]
+    pub stream: ::prost::alloc::string::String,
+    /// only the lines received in this time range, e.g. 2023-03-05T14:02:00Z, 14:02 or 10m
+    #[prost(string, tag = "5")]
+    pub since: ::prost::alloc::string::String,
+    #[prost(string, tag = "6")]
+    pub until: ::prost::alloc::string::String,
+    /// more services to merge the logs of, all the services when none is given
+    #[prost(string, repeated, tag = "7")]
+    pub services: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// only the lines matching this regular expression, or not matching it with invert_match
+    #[prost(string, tag = "8")]
+    pub grep: ::prost::alloc::string::String,
+    #[prost(bool, tag = "9")]
+    pub invert_match: bool,
+    /// only the lines of this level or a more severe one: trace, debug, info, warn or error
+    #[prost(string, tag = "10")]
+    pub level: ::prost::alloc::string::String,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct LogResponse {
     #[prost(string, tag = "1")]
     pub line: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub stream: ::prost::alloc::string::String,
+    /// RFC 3339, empty for the lines written before the logs were timestamped
+    #[prost(string, tag = "3")]
+    pub timestamp: ::prost::alloc::string::String,
+    #[prost(uint32, tag = "4")]
+    pub pid: u32,
+    /// name of the service
+    #[prost(string, tag = "5")]
+    pub service: ::prost::alloc::string::String,
+    /// exact bytes of the line when they aren't valid UTF-8, empty otherwise
+    #[prost(bytes = "vec", tag = "6")]
+    pub raw: ::prost::alloc::vec::Vec<u8>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -1047,12 +1107,65 @@ pub struct TailRequest {
     pub lines: u32,
     #[prost(bool, tag = "4")]
     pub follow: bool,
+    /// stdout (default), stderr or both
+    #[prost(string, tag = "5")]
+    pub stream: ::prost::alloc::string::String,
+    /// only the lines received in this time range, e.g. 2023-03-05T14:02:00Z, 14:02 or 10m
+    #[prost(string, tag = "6")]
+    pub since: ::prost::alloc::string::String,
+    #[prost(string, tag = "7")]
+    pub until: ::prost::alloc::string::String,
+    /// more services to merge the logs of, all the services when none is given
+    #[prost(string, repeated, tag = "8")]
+    pub services: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// only the lines matching this regular expression, or not matching it with invert_match
+    #[prost(string, tag = "9")]
+    pub grep: ::prost::alloc::string::String,
+    #[prost(bool, tag = "10")]
+    pub invert_match: bool,
+    /// only the lines of this level or a more severe one: trace, debug, info, warn or error
+    #[prost(string, tag = "11")]
+    pub level: ::prost::alloc::string::String,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct TailResponse {
     #[prost(string, tag = "1")]
     pub line: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub stream: ::prost::alloc::string::String,
+    /// RFC 3339, empty for the lines written before the logs were timestamped
+    #[prost(string, tag = "3")]
+    pub timestamp: ::prost::alloc::string::String,
+    #[prost(uint32, tag = "4")]
+    pub pid: u32,
+    /// name of the service
+    #[prost(string, tag = "5")]
+    pub service: ::prost::alloc::string::String,
+    /// exact bytes of the line when they aren't valid UTF-8, empty otherwise
+    #[prost(bytes = "vec", tag = "6")]
+    pub raw: ::prost::alloc::vec::Vec<u8>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AttachRequest {
+    #[prost(string, tag = "1")]
+    pub config_file_path: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AttachResponse {
+    #[prost(string, tag = "1")]
+    pub service: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub stream: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub line: ::prost::alloc::string::String,
+    #[prost(string, tag = "4")]
+    pub timestamp: ::prost::alloc::string::String,
+    /// exact bytes of the line when they aren't valid UTF-8, empty otherwise
+    #[prost(bytes = "vec", tag = "5")]
+    pub raw: ::prost::alloc::vec::Vec<u8>,
 }
 /// Generated client implementations.
 pub mod logging_service_client {
@@ -1167,6 +1280,28 @@ pub mod logging_service_client {
             );
             self.inner.server_streaming(request.into_request(), path, codec).await
         }
+        pub async fn attach(
+            &mut self,
+            request: impl tonic::IntoRequest<super::AttachRequest>,
+        ) -> Result<
+            tonic::Response<tonic::codec::Streaming<super::AttachResponse>>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/superviseur.v1alpha1.LoggingService/Attach",
+            );
+            self.inner.server_streaming(request.into_request(), path, codec).await
+        }
     }
 }
 /// Generated server implementations.
@@ -1196,6 +1331,16 @@ pub mod logging_service_server {
             &self,
             request: tonic::Request<super::TailRequest>,
         ) -> Result<tonic::Response<Self::TailStream>, tonic::Status>;
+        /// Server streaming response type for the Attach method.
+        type AttachStream: futures_core::Stream<
+                Item = Result<super::AttachResponse, tonic::Status>,
+            >
+            + Send
+            + 'static;
+        async fn attach(
+            &self,
+            request: tonic::Request<super::AttachRequest>,
+        ) -> Result<tonic::Response<Self::AttachStream>, tonic::Status>;
     }
     #[derive(Debug)]
     pub struct LoggingServiceServer<T: LoggingService> {
@@ -1334,6 +1479,45 @@ pub mod logging_service_server {
                     };
                     Box::pin(fut)
                 }
+                "/superviseur.v1alpha1.LoggingService/Attach" => {
+                    #[allow(non_camel_case_types)]
+                    struct AttachSvc<T: LoggingService>(pub Arc<T>);
+                    impl<
+                        T: LoggingService,
+                    > tonic::server::ServerStreamingService<super::AttachRequest>
+                    for AttachSvc<T> {
+                        type Response = super::AttachResponse;
+                        type ResponseStream = T::AttachStream;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::ResponseStream>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::AttachRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).attach(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = AttachSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            );
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 _ => {
                     Box::pin(async move {
                         Ok(
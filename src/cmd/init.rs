@@ -1,5 +1,35 @@
-use crate::types::configuration::ConfigFormat;
+use anyhow::Error;
+use owo_colors::OwoColorize;
 
-pub fn execute_init(cfg_format: ConfigFormat) {
-    todo!("init")
+use crate::{
+    config::find_superfiles,
+    types::{
+        configuration::{ConfigFormat, ConfigurationData},
+        SUPERFILE,
+    },
+};
+
+/// Creates a Superfile without services in the current directory, the project being
+/// named after the directory, unless there is already one.
+pub fn execute_init(cfg_format: ConfigFormat) -> Result<(), Error> {
+    if let Some(superfile) = find_superfiles()?.first() {
+        return Err(Error::msg(format!(
+            "{} already exists in current directory",
+            superfile.path.file_name().unwrap().to_string_lossy().bright_green()
+        )));
+    }
+    let project = std::env::current_dir()?
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "demo".to_string());
+    let config = ConfigurationData {
+        project,
+        services: vec![],
+    };
+    let serialized = cfg_format.serialize(&config)?;
+
+    let filename = format!("{}.{}", SUPERFILE, cfg_format.extensions()[0]);
+    std::fs::write(&filename, serialized)?;
+    println!("Created {} ✨", filename.bright_green());
+    Ok(())
 }
@@ -8,13 +8,13 @@ use crate::{
     api::superviseur::v1alpha1::{
         control_service_client::ControlServiceClient, ListRequest, LoadConfigRequest,
     },
-    types::{service::Service, UNIX_SOCKET_PATH, SUPERFILE}, config::verify_if_config_file_is_present,
+    types::{service::Service, UNIX_SOCKET_PATH}, config::verify_if_config_file_is_present,
 };
 
 pub async fn execute_list() -> Result<(), Error> {
-    verify_if_config_file_is_present()?;
+    let superfile = verify_if_config_file_is_present()?;
     let current_dir = std::env::current_dir()?;
-    let config = std::fs::read_to_string(current_dir.join(SUPERFILE))?;
+    let config = superfile.read()?;
     let channel = Endpoint::try_from("http://[::]:50051")?
     .connect_with_connector(service_fn(move |_: Uri| UnixStream::connect( UNIX_SOCKET_PATH)))
         .await
@@ -28,6 +28,7 @@ pub async fn execute_list() -> Result<(), Error> {
     let request = tonic::Request::new(LoadConfigRequest {
         config,
         file_path: current_dir.to_str().unwrap().to_string(),
+        format: superfile.format.to_string(),
     });
 
     client.load_config(request).await?;
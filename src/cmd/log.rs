@@ -1,4 +1,7 @@
+use std::io::Write;
+
 use anyhow::Error;
+use owo_colors::OwoColorize;
 use tokio::net::UnixStream;
 use tonic::transport::{ Endpoint, Uri};
 use tower::service_fn;
@@ -8,13 +11,26 @@ use crate::{
         control_service_client::ControlServiceClient, logging_service_client::LoggingServiceClient,
         LoadConfigRequest, LogRequest,
     },
-    types::{UNIX_SOCKET_PATH, SUPERFILE}, config::verify_if_config_file_is_present,
+    types::UNIX_SOCKET_PATH, config::verify_if_config_file_is_present,
+    cmd::up::COLORS,
 };
 
-pub async fn execute_log(name: &str, follow: bool) -> Result<(), Error> {
-    verify_if_config_file_is_present()?;
+/// Selection of the lines to show, made by the daemon, and how to print them.
+#[derive(Default)]
+pub struct LogOptions<'a> {
+    pub stream: &'a str,
+    pub since: Option<&'a str>,
+    pub until: Option<&'a str>,
+    pub grep: Option<&'a str>,
+    pub invert_match: bool,
+    pub level: Option<&'a str>,
+    pub timestamps: bool,
+}
+
+pub async fn execute_log(names: &[&str], follow: bool, options: LogOptions<'_>) -> Result<(), Error> {
+    let superfile = verify_if_config_file_is_present()?;
     let current_dir = std::env::current_dir()?;
-    let config = std::fs::read_to_string(current_dir.join(SUPERFILE))?;
+    let config = superfile.read()?;
     let channel = Endpoint::try_from("http://[::]:50051")?
     .connect_with_connector(service_fn(move |_: Uri| UnixStream::connect( UNIX_SOCKET_PATH)))
         .await
@@ -27,6 +43,7 @@ pub async fn execute_log(name: &str, follow: bool) -> Result<(), Error> {
     let request = tonic::Request::new(LoadConfigRequest {
         config,
         file_path: current_dir.to_str().unwrap().to_string(),
+        format: superfile.format.to_string(),
     });
 
     client.load_config(request).await?;
@@ -35,17 +52,82 @@ pub async fn execute_log(name: &str, follow: bool) -> Result<(), Error> {
     let mut client = LoggingServiceClient::new(channel);
 
     let request = tonic::Request::new(LogRequest {
-        service: name.to_string(),
+        service: String::new(),
+        services: names.iter().map(|name| name.to_string()).collect(),
         config_file_path: current_dir.to_str().unwrap().to_string(),
         follow,
+        stream: options.stream.to_string(),
+        since: options.since.unwrap_or_default().to_string(),
+        until: options.until.unwrap_or_default().to_string(),
+        grep: options.grep.unwrap_or_default().to_string(),
+        invert_match: options.invert_match,
+        level: options.level.unwrap_or_default().to_string(),
     });
 
     let response = client.log(request).await?;
     let mut stream = response.into_inner();
 
+    let mut printer = LogPrinter::new(names.len() != 1, options.timestamps);
     while let Some(message) = stream.message().await? {
-        println!("{}", message.line);
+        printer.print(&message.service, &message.stream, &message.timestamp, &message.line, &message.raw);
     }
 
     Ok(())
 }
+
+/// Prints the lines of the logs, prefixed with their service and stream when the logs
+/// of several services are merged, each service in its own colour.
+pub struct LogPrinter {
+    merged: bool,
+    timestamps: bool,
+    // services in the order their first line was printed
+    services: Vec<String>,
+}
+
+impl LogPrinter {
+    pub fn new(merged: bool, timestamps: bool) -> Self {
+        Self {
+            merged,
+            timestamps,
+            services: vec![],
+        }
+    }
+
+    /// Prints a line, as the exact bytes written by the service when given in `raw`.
+    pub fn print(&mut self, service: &str, stream: &str, timestamp: &str, line: &str, raw: &[u8]) {
+        let timestamp = match self.timestamps && !timestamp.is_empty() {
+            true => format!("{} ", timestamp),
+            false => String::new(),
+        };
+        if !self.merged {
+            write_line(&timestamp, line, raw);
+            return;
+        }
+
+        let index = match self.services.iter().position(|s| s == service) {
+            Some(index) => index,
+            None => {
+                self.services.push(service.to_string());
+                self.services.len() - 1
+            }
+        };
+        let width = self.services.iter().map(|s| s.len()).max().unwrap_or(0);
+        let prefix = format!("{:width$} {:6} |", service, stream, width = width);
+        let prefix = format!("{}{} ", timestamp, prefix.color(COLORS[index % COLORS.len()]));
+        write_line(&prefix, line, raw);
+    }
+}
+
+/// Prints a line after its prefix, as its exact bytes when they are given in `raw`
+/// since they aren't valid UTF-8.
+pub fn write_line(prefix: &str, line: &str, raw: &[u8]) {
+    let line = match raw.is_empty() {
+        true => line.as_bytes(),
+        false => raw,
+    };
+    let mut stdout = std::io::stdout().lock();
+    let _ = stdout
+        .write_all(prefix.as_bytes())
+        .and_then(|_| stdout.write_all(line))
+        .and_then(|_| stdout.write_all(b"\n"));
+}
@@ -3,6 +3,7 @@ pub mod init;
 pub mod list;
 pub mod log;
 pub mod new;
+pub mod outcome;
 pub mod ps;
 pub mod restart;
 pub mod start;
@@ -10,3 +11,4 @@ pub mod status;
 pub mod stop;
 pub mod tail;
 pub mod ui;
+pub mod up;
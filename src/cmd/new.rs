@@ -2,7 +2,10 @@ use std::{collections::HashMap, io::Write};
 
 use owo_colors::OwoColorize;
 
-use crate::types::configuration::{ConfigFormat, ConfigurationData, Service};
+use crate::types::{
+    configuration::{ConfigFormat, ConfigurationData, DependsOn, Service},
+    SUPERFILE,
+};
 
 pub fn execute_new(cfg_format: ConfigFormat) {
     let mut env = HashMap::new();
@@ -17,7 +20,7 @@ pub fn execute_new(cfg_format: ConfigFormat) {
             command: "ping $GITHUB_DOMAIN".to_string(),
             working_dir: "/tmp".to_string(),
             description: Some("Ping Service Example".to_string()),
-            depends_on: vec![],
+            depends_on: DependsOn::default(),
             dependencies: vec![],
             env,
             autostart: true,
@@ -26,19 +29,12 @@ pub fn execute_new(cfg_format: ConfigFormat) {
             port: 5060,
             stdout: "/tmp/demo-stdout.log".to_string(),
             stderr: "/tmp/demo-stderr.log".to_string(),
+            ..Default::default()
         }],
     };
-    let serialized = match cfg_format {
-        ConfigFormat::HCL => hcl::to_string(&config).unwrap(),
-        ConfigFormat::TOML => toml::to_string_pretty(&config).unwrap(),
-    };
-
-    let ext = match cfg_format {
-        ConfigFormat::HCL => "hcl",
-        ConfigFormat::TOML => "toml",
-    };
+    let serialized = cfg_format.serialize(&config).unwrap();
 
-    let filename = format!("Superfile.{}", ext);
+    let filename = format!("{}.{}", SUPERFILE, cfg_format.extensions()[0]);
     let mut file = std::fs::File::create(&filename).unwrap();
     file.write_all(serialized.as_bytes()).unwrap();
     println!("Created {} ✨", filename.bright_green());
@@ -0,0 +1,31 @@
+use owo_colors::OwoColorize;
+
+use crate::api::superviseur::v1alpha1::ServiceOutcome;
+
+/// Prints the outcome of a command for each service, the failures are returned as
+/// an error by the commands.
+pub fn print_outcomes(outcomes: &[ServiceOutcome]) {
+    for outcome in outcomes {
+        match outcome.outcome.as_str() {
+            "started" => println!(
+                "{} {} started (pid {})",
+                "●".bright_green(),
+                outcome.service,
+                outcome.pid
+            ),
+            "already_running" => println!(
+                "{} {} is already running (pid {})",
+                "●".bright_green(),
+                outcome.service,
+                outcome.pid
+            ),
+            "stopped" => println!("{} {} stopped", "●".bright_red(), outcome.service),
+            "not_running" => println!(
+                "{} {} is not running",
+                "●".bright_yellow(),
+                outcome.service
+            ),
+            _ => {}
+        }
+    }
+}
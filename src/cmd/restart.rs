@@ -7,13 +7,14 @@ use crate::{
     api::superviseur::v1alpha1::{
         control_service_client::ControlServiceClient, LoadConfigRequest, RestartRequest,
     },
-    types::{UNIX_SOCKET_PATH, SUPERFILE}, config::verify_if_config_file_is_present,
+    cmd::outcome::print_outcomes,
+    types::UNIX_SOCKET_PATH, config::verify_if_config_file_is_present,
 };
 
 pub async fn execute_restart(name: Option<&str>) -> Result<(), Error> {
-    verify_if_config_file_is_present()?;
+    let superfile = verify_if_config_file_is_present()?;
     let current_dir = std::env::current_dir()?;
-    let config = std::fs::read_to_string(current_dir.join(SUPERFILE))?;
+    let config = superfile.read()?;
     let channel = Endpoint::try_from("http://[::]:50051")?
     .connect_with_connector(service_fn(move |_: Uri| UnixStream::connect( UNIX_SOCKET_PATH)))
         .await
@@ -26,6 +27,7 @@ pub async fn execute_restart(name: Option<&str>) -> Result<(), Error> {
     let request = tonic::Request::new(LoadConfigRequest {
         config,
         file_path: current_dir.to_str().unwrap().to_string(),
+        format: superfile.format.to_string(),
     });
 
     client.load_config(request).await?;
@@ -37,6 +39,10 @@ pub async fn execute_restart(name: Option<&str>) -> Result<(), Error> {
         config_file_path: current_dir.to_str().unwrap().to_string(),
     });
 
-    client.restart(request).await?;
+    let response = client.restart(request).await?.into_inner();
+    print_outcomes(&response.outcomes);
+    if !response.success {
+        return Err(Error::msg(response.error));
+    }
     Ok(())
 }
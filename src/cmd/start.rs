@@ -2,7 +2,8 @@ use crate::{
     api::superviseur::v1alpha1::{
         control_service_client::ControlServiceClient, LoadConfigRequest, StartRequest,
     },
-    types::{UNIX_SOCKET_PATH, SUPERFILE}, config::verify_if_config_file_is_present,
+    cmd::outcome::print_outcomes,
+    types::UNIX_SOCKET_PATH, config::verify_if_config_file_is_present,
 };
 use anyhow::Error;
 use tokio::net::UnixStream;
@@ -10,9 +11,9 @@ use tonic::transport::{ Endpoint, Uri};
 use tower::service_fn;
 
 pub async fn execute_start(name: Option<&str>) -> Result<(), Error> {
-    verify_if_config_file_is_present()?;
+    let superfile = verify_if_config_file_is_present()?;
     let current_dir = std::env::current_dir()?;
-    let config = std::fs::read_to_string(current_dir.join(SUPERFILE))?;
+    let config = superfile.read()?;
     let channel = Endpoint::try_from("http://[::]:50051")?
     .connect_with_connector(service_fn(move |_: Uri| UnixStream::connect( UNIX_SOCKET_PATH)))
         .await
@@ -25,6 +26,7 @@ pub async fn execute_start(name: Option<&str>) -> Result<(), Error> {
     let request = tonic::Request::new(LoadConfigRequest {
         config,
         file_path: current_dir.to_str().unwrap().to_string(),
+        format: superfile.format.to_string(),
     });
 
     client.load_config(request).await?;
@@ -36,7 +38,11 @@ pub async fn execute_start(name: Option<&str>) -> Result<(), Error> {
         config_file_path: current_dir.to_str().unwrap().to_string(),
     });
 
-    client.start(request).await?;
+    let response = client.start(request).await?.into_inner();
+    print_outcomes(&response.outcomes);
+    if !response.success {
+        return Err(Error::msg(response.error));
+    }
 
     Ok(())
 }
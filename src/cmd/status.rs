@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use anyhow::Error;
 use chrono::{DateTime, Utc};
 use owo_colors::OwoColorize;
@@ -9,13 +11,13 @@ use crate::{
     api::superviseur::v1alpha1::{
         control_service_client::ControlServiceClient, LoadConfigRequest, StatusRequest,
     },
-    types::{process::format_duration, UNIX_SOCKET_PATH, SUPERFILE}, config::verify_if_config_file_is_present,
+    types::{process::format_duration, UNIX_SOCKET_PATH}, config::verify_if_config_file_is_present,
 };
 
 pub async fn execute_status(name: &str) -> Result<(), Error> {
-    verify_if_config_file_is_present()?;
+    let superfile = verify_if_config_file_is_present()?;
     let current_dir = std::env::current_dir()?;
-    let config = std::fs::read_to_string(current_dir.join(SUPERFILE))?;
+    let config = superfile.read()?;
     let channel = Endpoint::try_from("http://[::]:50051")?
     .connect_with_connector(service_fn(move |_: Uri| UnixStream::connect( UNIX_SOCKET_PATH)))
         .await
@@ -28,6 +30,7 @@ pub async fn execute_status(name: &str) -> Result<(), Error> {
     let request = tonic::Request::new(LoadConfigRequest {
         config,
         file_path: current_dir.to_str().unwrap().to_string(),
+        format: superfile.format.to_string(),
     });
 
     client.load_config(request).await?;
@@ -44,7 +47,7 @@ pub async fn execute_status(name: &str) -> Result<(), Error> {
 
     let status_dot = match process.state.as_str() {
         "Running" => format!("{}", "●".bright_green()),
-        "Stopped" => format!("{}", "●".bright_red()),
+        "Stopped" | "Fatal" | "Failed" => format!("{}", "●".bright_red()),
         _ => format!("{}", "●".bright_yellow()),
     };
     match process.description.len() {
@@ -69,8 +72,35 @@ pub async fn execute_status(name: &str) -> Result<(), Error> {
 
     if process.state == "Running" {
         println!("{:>15} {}", "PID:", process.pid);
+        match process.health.as_str() {
+            "Healthy" => println!("{:>15} {}", "Health:", "Healthy".bright_green()),
+            "Unhealthy" => println!("{:>15} {}", "Health:", "Unhealthy".bright_red()),
+            "Starting" => println!("{:>15} {}", "Health:", "Starting".bright_yellow()),
+            _ => {}
+        }
     }
 
+    if process.state == "Failed" {
+        println!("{:>15} {}", "Error:", process.error.bright_red());
+    }
+
+    if !process.exited_at.is_empty() {
+        let exited_at: DateTime<Utc> = process.exited_at.parse().unwrap();
+        let reason = match (process.exit_code, process.exit_signal.as_str()) {
+            (Some(code), _) => format!("code {}", code),
+            (None, "") => "unknown".to_string(),
+            (None, signal) => format!("killed by {}", signal),
+        };
+        println!(
+            "{:>15} {} at {}; {}",
+            "Last exit:",
+            reason,
+            exited_at,
+            format_duration(Utc::now() - exited_at)
+        );
+    }
+
+    println!("{:>15} {}", "Restarts:", process.restarts);
     println!("{:>15} {}", "Command:", process.command);
     println!("{:>15} {}", "Directory:", process.working_directory);
     println!("{:>15} {}", "Log:", process.log_file);
@@ -79,5 +109,21 @@ pub async fn execute_status(name: &str) -> Result<(), Error> {
     println!("{:>15} {}", "Type:", process.r#type);
     println!("{:>15} {}", "Envs:", process.env.join(", "));
 
+    if !process.children.is_empty() {
+        println!("{:>15} {} {}", "Processes:", process.pid, process.command);
+        let mut depths = HashMap::from([(process.pid, 0)]);
+        for child in &process.children {
+            let depth = depths.get(&child.ppid).map(|d| d + 1).unwrap_or(1);
+            depths.insert(child.pid, depth);
+            println!(
+                "{:>15} {}└─{} {}",
+                "",
+                "  ".repeat(depth - 1),
+                child.pid,
+                child.command
+            );
+        }
+    }
+
     Ok(())
 }
@@ -8,13 +8,19 @@ use crate::{
         control_service_client::ControlServiceClient, logging_service_client::LoggingServiceClient,
         LoadConfigRequest, TailRequest,
     },
-    types::{UNIX_SOCKET_PATH, SUPERFILE}, config::verify_if_config_file_is_present,
+    types::UNIX_SOCKET_PATH, config::verify_if_config_file_is_present,
+    cmd::log::{LogOptions, LogPrinter},
 };
 
-pub async fn execute_tail(name: &str, follow: bool, lines: usize) -> Result<(), Error> {
-    verify_if_config_file_is_present()?;
+pub async fn execute_tail(
+    names: &[&str],
+    follow: bool,
+    lines: usize,
+    options: LogOptions<'_>,
+) -> Result<(), Error> {
+    let superfile = verify_if_config_file_is_present()?;
     let current_dir = std::env::current_dir()?;
-    let config = std::fs::read_to_string(current_dir.join(SUPERFILE))?;
+    let config = superfile.read()?;
     let channel = Endpoint::try_from("http://[::]:50051")?
     .connect_with_connector(service_fn(move |_: Uri| UnixStream::connect( UNIX_SOCKET_PATH)))
         .await
@@ -27,6 +33,7 @@ pub async fn execute_tail(name: &str, follow: bool, lines: usize) -> Result<(),
     let request = tonic::Request::new(LoadConfigRequest {
         config,
         file_path: current_dir.to_str().unwrap().to_string(),
+        format: superfile.format.to_string(),
     });
 
     client.load_config(request).await?;
@@ -35,17 +42,25 @@ pub async fn execute_tail(name: &str, follow: bool, lines: usize) -> Result<(),
     let mut client = LoggingServiceClient::new(channel);
 
     let request = tonic::Request::new(TailRequest {
-        service: name.to_string(),
+        service: String::new(),
+        services: names.iter().map(|name| name.to_string()).collect(),
         config_file_path: current_dir.to_str().unwrap().to_string(),
         follow,
         lines: lines as u32,
+        stream: options.stream.to_string(),
+        since: options.since.unwrap_or_default().to_string(),
+        until: options.until.unwrap_or_default().to_string(),
+        grep: options.grep.unwrap_or_default().to_string(),
+        invert_match: options.invert_match,
+        level: options.level.unwrap_or_default().to_string(),
     });
 
     let response = client.tail(request).await?;
     let mut stream = response.into_inner();
 
+    let mut printer = LogPrinter::new(names.len() != 1, options.timestamps);
     while let Some(message) = stream.message().await? {
-        println!("{}", message.line);
+        printer.print(&message.service, &message.stream, &message.timestamp, &message.line, &message.raw);
     }
 
     Ok(())
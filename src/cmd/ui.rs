@@ -7,12 +7,12 @@ use tokio::net::UnixStream;
 use tonic::transport::Endpoint;
 use tower::service_fn;
 
-use crate::{types::{BANNER, UNIX_SOCKET_PATH, SUPERFILE},  config::verify_if_config_file_is_present, api::superviseur::v1alpha1::{core_service_client::CoreServiceClient,  control_service_client::ControlServiceClient, LoadConfigRequest, StartWebDashboardRequest}};
+use crate::{types::{BANNER, UNIX_SOCKET_PATH},  config::verify_if_config_file_is_present, api::superviseur::v1alpha1::{core_service_client::CoreServiceClient,  control_service_client::ControlServiceClient, LoadConfigRequest, StartWebDashboardRequest}};
 
 pub async fn execute_ui() -> Result<(), Error> {
-    verify_if_config_file_is_present()?;
+    let superfile = verify_if_config_file_is_present()?;
     let current_dir = std::env::current_dir()?;
-    let config = std::fs::read_to_string(current_dir.join(SUPERFILE))?;
+    let config = superfile.read()?;
     let channel = Endpoint::try_from("http://[::]:50051")?
     .connect_with_connector(service_fn(move |_: Uri| UnixStream::connect(UNIX_SOCKET_PATH)))
         .await
@@ -26,6 +26,7 @@ pub async fn execute_ui() -> Result<(), Error> {
     let request = tonic::Request::new(LoadConfigRequest {
         config,
         file_path: current_dir.to_str().unwrap().to_string(),
+        format: superfile.format.to_string(),
     });
     client.load_config(request).await?;
 
@@ -0,0 +1,152 @@
+use std::time::Duration;
+
+use anyhow::Error;
+use chrono::{DateTime, Local};
+use owo_colors::{AnsiColors, OwoColorize};
+use tokio::net::UnixStream;
+use tonic::transport::{Endpoint, Uri};
+use tower::service_fn;
+
+use crate::{
+    api::superviseur::v1alpha1::{
+        control_service_client::ControlServiceClient, logging_service_client::LoggingServiceClient,
+        AttachRequest, AttachResponse, LoadConfigRequest, StartRequest, StopRequest,
+    },
+    cmd::{log::write_line, outcome::print_outcomes, start::execute_start},
+    config::verify_if_config_file_is_present,
+    types::UNIX_SOCKET_PATH,
+};
+
+/// Colours of the service names, in the order of the services.
+pub const COLORS: [AnsiColors; 6] = [
+    AnsiColors::Cyan,
+    AnsiColors::Yellow,
+    AnsiColors::Green,
+    AnsiColors::Magenta,
+    AnsiColors::Blue,
+    AnsiColors::BrightCyan,
+];
+
+/// Starts all the services. Once attached, prints their output until Ctrl-C, which
+/// stops them.
+pub async fn execute_up(attach: bool) -> Result<(), Error> {
+    if !attach {
+        return execute_start(None).await;
+    }
+
+    let superfile = verify_if_config_file_is_present()?;
+    let current_dir = std::env::current_dir()?;
+    let config = superfile.read()?;
+    let channel = Endpoint::try_from("http://[::]:50051")?
+        .connect_with_connector(service_fn(move |_: Uri| UnixStream::connect(UNIX_SOCKET_PATH)))
+        .await
+        .map_err(|_| {
+            Error::msg(format!(
+                "Cannot connect to the Superviseur daemon at unix:{}. Is the superviseur daemon running?",
+                UNIX_SOCKET_PATH
+            ))
+        })?;
+
+    let mut client = ControlServiceClient::new(channel.clone());
+
+    let request = tonic::Request::new(LoadConfigRequest {
+        config,
+        file_path: current_dir.to_str().unwrap().to_string(),
+        format: superfile.format.to_string(),
+    });
+
+    client.load_config(request).await?;
+
+    // attach before starting the services, not to miss their first lines
+    let mut logging = LoggingServiceClient::new(channel);
+    let request = tonic::Request::new(AttachRequest {
+        config_file_path: current_dir.to_str().unwrap().to_string(),
+    });
+    let mut stream = logging.attach(request).await?.into_inner();
+
+    let request = tonic::Request::new(StartRequest {
+        name: String::new(),
+        config_file_path: current_dir.to_str().unwrap().to_string(),
+    });
+
+    let response = client.start(request).await?.into_inner();
+    print_outcomes(&response.outcomes);
+    // the services which could be started are attached anyway
+    if !response.success {
+        println!("{}", response.error.bright_red());
+    }
+
+    let services = response
+        .outcomes
+        .iter()
+        .map(|o| o.service.clone())
+        .collect::<Vec<String>>();
+
+    // wait for Ctrl-C, then for the services to be stopped while printing their last lines
+    let interrupted = tokio::signal::ctrl_c();
+    tokio::pin!(interrupted);
+    loop {
+        tokio::select! {
+            message = stream.message() => match message? {
+                Some(message) => print_line(&message, &services),
+                None => return Ok(()),
+            },
+            result = &mut interrupted => {
+                result?;
+                break;
+            }
+        }
+    }
+
+    let request = tonic::Request::new(StopRequest {
+        name: String::new(),
+        config_file_path: current_dir.to_str().unwrap().to_string(),
+    });
+    let stop = client.stop(request);
+    tokio::pin!(stop);
+    let response = loop {
+        tokio::select! {
+            message = stream.message() => match message? {
+                Some(message) => print_line(&message, &services),
+                None => return Ok(()),
+            },
+            response = &mut stop => break response?.into_inner(),
+            // interrupted again, don't wait for the services to be stopped
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+    };
+
+    // the log files are polled, print what the services wrote before exiting
+    let drained = tokio::time::sleep(Duration::from_millis(300));
+    tokio::pin!(drained);
+    loop {
+        tokio::select! {
+            message = stream.message() => match message? {
+                Some(message) => print_line(&message, &services),
+                None => break,
+            },
+            _ = &mut drained => break,
+        }
+    }
+    print_outcomes(&response.outcomes);
+    if !response.success {
+        return Err(Error::msg(response.error));
+    }
+
+    Ok(())
+}
+
+// prints a line prefixed with the time it was written and the name of its service
+fn print_line(message: &AttachResponse, services: &[String]) {
+    let width = services.iter().map(|s| s.len()).max().unwrap_or(0);
+    let index = services
+        .iter()
+        .position(|s| s == &message.service)
+        .unwrap_or(0);
+    let time = DateTime::parse_from_rfc3339(&message.timestamp)
+        .map(|t| t.with_timezone(&Local).format("%H:%M:%S").to_string())
+        .unwrap_or_default();
+    let prefix = format!("{} {:width$} |", time, message.service, width = width);
+    let prefix = format!("{} ", prefix.color(COLORS[index % COLORS.len()]));
+    write_line(&prefix, &message.line, &message.raw);
+}
@@ -1,17 +1,63 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use anyhow::Error;
 use owo_colors::OwoColorize;
 
-use crate::types::SUPERFILE;
+use crate::types::{configuration::ConfigFormat, SUPERFILE};
 
-pub fn verify_if_config_file_is_present() -> Result<(), Error> {
-    if !Path::new("Superfile.hcl").exists() {
-        return Err(Error::msg(format!(
+const FORMATS: [ConfigFormat; 4] = [
+    ConfigFormat::HCL,
+    ConfigFormat::TOML,
+    ConfigFormat::JSON,
+    ConfigFormat::YAML,
+];
+
+/// Superfile found in the current directory.
+pub struct Superfile {
+    pub path: PathBuf,
+    pub format: ConfigFormat,
+}
+
+impl Superfile {
+    pub fn read(&self) -> Result<String, Error> {
+        Ok(std::fs::read_to_string(&self.path)?)
+    }
+}
+
+/// Superfiles of the current directory, in any format.
+pub fn find_superfiles() -> Result<Vec<Superfile>, Error> {
+    let mut found = vec![];
+    for format in FORMATS {
+        for extension in format.extensions() {
+            let path = PathBuf::from(format!("{}.{}", SUPERFILE, extension));
+            if Path::new(&path).exists() {
+                found.push(Superfile {
+                    path: std::env::current_dir()?.join(path),
+                    format,
+                });
+            }
+        }
+    }
+    Ok(found)
+}
+
+pub fn verify_if_config_file_is_present() -> Result<Superfile, Error> {
+    let mut found = find_superfiles()?;
+    match found.len() {
+        0 => Err(Error::msg(format!(
             "{} not found in current directory, please create one by running {}",
-            SUPERFILE.bright_green(),
+            format!("{}.{{hcl,toml,json,yaml,yml}}", SUPERFILE).bright_green(),
             "`superviseur new`".cyan()
-        )));
+        ))),
+        1 => Ok(found.remove(0)),
+        _ => Err(Error::msg(format!(
+            "Several Superfiles found in current directory: {}, please keep only one",
+            found
+                .iter()
+                .map(|superfile| superfile.path.file_name().unwrap().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join(", ")
+                .bright_green()
+        ))),
     }
-    Ok(())
 }
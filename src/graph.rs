@@ -0,0 +1,201 @@
+use anyhow::Error;
+
+use crate::types::configuration::Service;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Visit {
+    New,
+    InProgress,
+    Done,
+}
+
+/// Graph of the services of a project, built from the ids in `Service::dependencies`.
+pub struct DependencyGraph {
+    services: Vec<Service>,
+    // index of the dependencies of each service
+    edges: Vec<Vec<usize>>,
+}
+
+impl DependencyGraph {
+    /// Fails if a dependency is missing or if the dependencies contain a cycle.
+    pub fn new(services: Vec<Service>) -> Result<Self, Error> {
+        let mut edges = vec![];
+        for service in &services {
+            let mut dependencies = vec![];
+            for id in &service.dependencies {
+                match services.iter().position(|s| s.id.as_ref() == Some(id)) {
+                    Some(index) => dependencies.push(index),
+                    None => {
+                        return Err(Error::msg(format!(
+                            "Service {} depends on an unknown service ({})",
+                            service.name, id
+                        )))
+                    }
+                }
+            }
+            edges.push(dependencies);
+        }
+
+        let graph = Self { services, edges };
+        graph.check_cycles()?;
+        Ok(graph)
+    }
+
+    fn check_cycles(&self) -> Result<(), Error> {
+        let mut visits = vec![Visit::New; self.services.len()];
+        let mut path = vec![];
+        for index in 0..self.services.len() {
+            self.visit(index, &mut visits, &mut path)?;
+        }
+        Ok(())
+    }
+
+    fn visit(
+        &self,
+        index: usize,
+        visits: &mut [Visit],
+        path: &mut Vec<usize>,
+    ) -> Result<(), Error> {
+        match visits[index] {
+            Visit::Done => return Ok(()),
+            Visit::InProgress => {
+                let start = path.iter().position(|i| *i == index).unwrap();
+                let cycle = path[start..]
+                    .iter()
+                    .chain(std::iter::once(&index))
+                    .map(|i| self.services[*i].name.clone())
+                    .collect::<Vec<String>>();
+                return Err(Error::msg(format!(
+                    "Dependency cycle detected: {}",
+                    cycle.join(" -> ")
+                )));
+            }
+            Visit::New => {}
+        }
+
+        visits[index] = Visit::InProgress;
+        path.push(index);
+        for dependency in &self.edges[index] {
+            self.visit(*dependency, visits, path)?;
+        }
+        path.pop();
+        visits[index] = Visit::Done;
+        Ok(())
+    }
+
+    /// Start layers in reverse order, each layer only being a dependency of the
+    /// previous ones.
+    pub fn stop_layers(&self) -> Vec<Vec<Service>> {
+        let mut layers = self.start_layers();
+        layers.reverse();
+        layers
+    }
+
+    /// Services grouped in layers: the services of a layer only depend on services
+    /// of the previous layers, so they can be started in parallel.
+    pub fn start_layers(&self) -> Vec<Vec<Service>> {
+        let mut layer_of: Vec<Option<usize>> = vec![None; self.services.len()];
+        let mut layers: Vec<Vec<Service>> = vec![];
+
+        while layer_of.iter().any(|l| l.is_none()) {
+            let layer = (0..self.services.len())
+                .filter(|i| layer_of[*i].is_none())
+                .filter(|i| {
+                    self.edges[*i]
+                        .iter()
+                        .all(|d| matches!(layer_of[*d], Some(l) if l < layers.len()))
+                })
+                .collect::<Vec<usize>>();
+            for index in &layer {
+                layer_of[*index] = Some(layers.len());
+            }
+            layers.push(
+                layer
+                    .into_iter()
+                    .map(|i| self.services[i].clone())
+                    .collect(),
+            );
+        }
+        layers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service(name: &str, dependencies: &[&str]) -> Service {
+        Service {
+            id: Some(name.to_string()),
+            name: name.to_string(),
+            dependencies: dependencies.iter().map(|d| d.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn names(layers: Vec<Vec<Service>>) -> Vec<Vec<String>> {
+        layers
+            .into_iter()
+            .map(|layer| layer.into_iter().map(|s| s.name).collect())
+            .collect()
+    }
+
+    #[test]
+    fn diamond_is_started_layer_by_layer() {
+        let graph = DependencyGraph::new(vec![
+            service("app", &["cache", "db"]),
+            service("cache", &["network"]),
+            service("db", &["network"]),
+            service("network", &[]),
+        ])
+        .unwrap();
+        assert_eq!(
+            names(graph.start_layers()),
+            vec![vec!["network"], vec!["cache", "db"], vec!["app"]]
+        );
+        assert_eq!(
+            names(graph.stop_layers()),
+            vec![vec!["app"], vec!["cache", "db"], vec!["network"]]
+        );
+    }
+
+    #[test]
+    fn independent_services_share_a_layer() {
+        let graph = DependencyGraph::new(vec![service("a", &[]), service("b", &[])]).unwrap();
+        assert_eq!(names(graph.start_layers()), vec![vec!["a", "b"]]);
+    }
+
+    #[test]
+    fn cycle_is_reported_with_its_path() {
+        let error = DependencyGraph::new(vec![
+            service("web", &["api"]),
+            service("api", &["db"]),
+            service("db", &["api"]),
+        ])
+        .err()
+        .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Dependency cycle detected: api -> db -> api"
+        );
+    }
+
+    #[test]
+    fn self_dependency_is_a_cycle() {
+        let error = DependencyGraph::new(vec![service("a", &["a"])])
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "Dependency cycle detected: a -> a");
+    }
+
+    #[test]
+    fn unknown_dependency_is_rejected() {
+        let error = DependencyGraph::new(vec![service("a", &["b"])])
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Service a depends on an unknown service (b)"
+        );
+    }
+}
@@ -1,9 +1,4 @@
-use std::{
-    collections::HashMap,
-    sync::{Arc, Mutex},
-    thread,
-    time::Duration,
-};
+use std::sync::{Arc, Mutex};
 
 use async_graphql::{Context, Error, Object, Subscription, ID};
 use futures_util::Stream;
@@ -11,16 +6,17 @@ use tokio::sync::mpsc;
 
 use crate::{
     graphql::{schema::objects::subscriptions::ServiceStarted, simple_broker::SimpleBroker},
-    superviseur::SuperviseurCommand,
-    types::{self, configuration::ConfigurationData, process::State},
+    registry::Registry,
+    superviseur::{send_and_wait, CommandOutcome, SuperviseurCommand},
+    types::process::State,
 };
 
 use super::objects::{
     process::Process,
     service::Service,
     subscriptions::{
-        AllServicesRestarted, AllServicesStarted, AllServicesStopped, ServiceRestarted,
-        ServiceStopped,
+        AllServicesRestarted, AllServicesStarted, AllServicesStopped, ServiceHealthChanged,
+        ServiceRestarted, ServiceStopped,
     },
 };
 
@@ -30,52 +26,31 @@ pub struct ControlQuery;
 #[Object]
 impl ControlQuery {
     async fn status(&self, ctx: &Context<'_>, id: ID) -> Result<Process, Error> {
-        let processes = ctx
-            .data::<Arc<Mutex<Vec<(types::process::Process, String)>>>>()
-            .unwrap();
-
-        let processes = processes.lock().unwrap();
-
-        let process = processes
-            .iter()
-            .find(|(p, _)| p.service_id.clone() == id.to_string())
-            .map(|(p, _)| p.clone());
+        let registry = ctx.data::<Arc<Mutex<Registry>>>().unwrap();
+        let registry = registry.lock().unwrap();
 
-        match process {
-            Some(p) => Ok(Process::from(p)),
+        match registry.find_process(&id) {
+            Some(p) => Ok(Process::from(p.clone())),
             None => Err(Error::new("Process not found")),
         }
     }
 
     async fn services(&self, ctx: &Context<'_>) -> Result<Vec<Service>, Error> {
         let config_file_path = ctx.data::<String>().unwrap();
-        let processes = ctx
-            .data::<Arc<Mutex<Vec<(types::process::Process, String)>>>>()
-            .unwrap();
-        let config_map = ctx
-            .data::<Arc<Mutex<HashMap<String, ConfigurationData>>>>()
-            .unwrap();
-
-        let processes = processes.lock().unwrap();
+        let registry = ctx.data::<Arc<Mutex<Registry>>>().unwrap();
+        let registry = registry.lock().unwrap();
 
-        let config_map = config_map.lock().unwrap();
-
-        if !config_map.contains_key(config_file_path.as_str()) {
-            return Err(Error::new("Configuration file not found"));
-        }
+        let project = registry
+            .project_by_path(config_file_path)
+            .ok_or(Error::new("Configuration file not found"))?;
 
-        let config = config_map.get(config_file_path.as_str()).unwrap();
-
-        let services = config.services.clone();
+        let services = project.config.services.clone();
         let mut services = services.iter().map(Service::from).collect::<Vec<Service>>();
 
         for service in services.iter_mut() {
-            let process = processes
-                .iter()
-                .find(|(p, _)| p.name == service.name)
-                .map(|(p, _)| p);
-            if let Some(process) = process {
+            if let Some(process) = project.process(&service.id) {
                 service.status = process.state.to_string().to_uppercase();
+                service.health = process.health.to_string();
             } else {
                 service.status = "stopped".to_string();
             }
@@ -85,65 +60,41 @@ impl ControlQuery {
     }
 
     async fn processes(&self, ctx: &Context<'_>) -> Result<Vec<Process>, Error> {
-        let processes = ctx
-            .data::<Arc<Mutex<Vec<(types::process::Process, String)>>>>()
-            .unwrap();
-
-        let processes = processes.lock().unwrap();
-        Ok(processes
-            .iter()
-            .filter(|(p, _)| p.state != State::Stopped)
-            .map(|(p, _)| Process::from(p.clone()))
+        let registry = ctx.data::<Arc<Mutex<Registry>>>().unwrap();
+        let registry = registry.lock().unwrap();
+        Ok(registry
+            .processes()
+            .filter(|p| p.state != State::Stopped)
+            .map(|p| Process::from(p.clone()))
             .collect())
     }
 
     async fn service(&self, ctx: &Context<'_>, id: ID) -> Result<Service, Error> {
         let config_file_path = ctx.data::<String>().unwrap();
-        let processes = ctx
-            .data::<Arc<Mutex<Vec<(types::process::Process, String)>>>>()
-            .unwrap();
-        let config_map = ctx
-            .data::<Arc<Mutex<HashMap<String, ConfigurationData>>>>()
-            .unwrap();
-
-        let config_map = config_map.lock().unwrap();
-
-        if !config_map.contains_key(config_file_path.as_str()) {
-            return Err(Error::new("Configuration file not found"));
-        }
+        let registry = ctx.data::<Arc<Mutex<Registry>>>().unwrap();
+        let registry = registry.lock().unwrap();
 
-        let config = config_map.get(config_file_path.as_str()).unwrap();
+        let project = registry
+            .project_by_path(config_file_path)
+            .ok_or(Error::new("Configuration file not found"))?;
 
-        let processes = processes.lock().unwrap();
-
-        match processes
+        let service = project
+            .config
+            .services
             .iter()
-            .find(|(p, _)| p.service_id.clone() == id.to_string())
-        {
-            Some((process, _)) => {
-                let service = config
-                    .services
-                    .iter()
-                    .find(|s| s.id == Some(id.to_string()))
-                    .ok_or(Error::new("Service not found"))?;
-
-                Ok(Service {
-                    status: process.state.to_string(),
-                    ..Service::from(service)
-                })
-            }
-            None => {
-                let service = config
-                    .services
-                    .iter()
-                    .find(|s| s.id == Some(id.to_string()))
-                    .ok_or(Error::new("Service not found"))?;
-
-                Ok(Service {
-                    status: "stopped".to_string(),
-                    ..Service::from(service)
-                })
-            }
+            .find(|s| s.id == Some(id.to_string()))
+            .ok_or(Error::new("Service not found"))?;
+
+        match project.process(&id) {
+            Some(process) => Ok(Service {
+                status: process.state.to_string(),
+                health: process.health.to_string(),
+                ..Service::from(service)
+            }),
+            None => Ok(Service {
+                status: "stopped".to_string(),
+                ..Service::from(service)
+            }),
         }
     }
 }
@@ -158,35 +109,26 @@ impl ControlMutation {
         let cmd_tx = ctx
             .data::<mpsc::UnboundedSender<SuperviseurCommand>>()
             .unwrap();
-        let processes = ctx
-            .data::<Arc<Mutex<Vec<(types::process::Process, String)>>>>()
-            .unwrap();
-        let config_map = ctx
-            .data::<Arc<Mutex<HashMap<String, ConfigurationData>>>>()
-            .unwrap();
+        let registry = ctx.data::<Arc<Mutex<Registry>>>().unwrap();
 
-        let config_map = config_map.lock().unwrap();
-
-        if !config_map.contains_key(config_file_path.as_str()) {
-            return Err(Error::new("Configuration file not found"));
-        }
-
-        let config = config_map.get(config_file_path.as_str()).unwrap();
+        let config = registry
+            .lock()
+            .unwrap()
+            .config_by_path(config_file_path)
+            .cloned()
+            .ok_or(Error::new("Configuration file not found"))?;
 
         if id.is_none() {
-            for service in &config.services {
-                cmd_tx
-                    .send(SuperviseurCommand::Start(
-                        service.clone(),
-                        config.project.clone(),
-                    ))
-                    .unwrap();
-            }
+            let outcomes = send_and_wait(cmd_tx, |reply| {
+                SuperviseurCommand::StartAll(config.project.clone(), Some(reply))
+            })
+            .await?;
 
             let services = config.services.clone();
             let services = services.iter().map(Service::from).collect::<Vec<Service>>();
             SimpleBroker::publish(AllServicesStarted { payload: services });
 
+            check_outcomes(outcomes)?;
             return Ok(Process {
                 ..Default::default()
             });
@@ -198,16 +140,15 @@ impl ControlMutation {
             .find(|s| s.id == id.as_ref().map(|x| x.to_string()))
             .ok_or(Error::new("Service not found"))?;
 
-        cmd_tx.send(SuperviseurCommand::Start(
-            service.clone(),
-            config.project.clone(),
-        ))?;
+        let outcome = send_and_wait(cmd_tx, |reply| {
+            SuperviseurCommand::Start(service.clone(), config.project.clone(), Some(reply))
+        })
+        .await?;
+        check_outcomes(vec![(service.name.clone(), outcome)])?;
 
-        thread::sleep(Duration::from_secs(1));
-        let processes = processes.lock().unwrap();
-        let (process, _) = processes
-            .iter()
-            .find(|(p, _)| Some(p.service_id.clone()) == service.id)
+        let registry = registry.lock().unwrap();
+        let process = registry
+            .process(&config.project, &service.id.clone().unwrap_or_default())
             .ok_or(Error::new("Process not found"))?;
 
         Ok(Process::from(process.clone()))
@@ -218,33 +159,24 @@ impl ControlMutation {
         let cmd_tx = ctx
             .data::<mpsc::UnboundedSender<SuperviseurCommand>>()
             .unwrap();
-        let processes = ctx
-            .data::<Arc<Mutex<Vec<(types::process::Process, String)>>>>()
-            .unwrap();
-        let config_map = ctx
-            .data::<Arc<Mutex<HashMap<String, ConfigurationData>>>>()
-            .unwrap();
+        let registry = ctx.data::<Arc<Mutex<Registry>>>().unwrap();
 
-        let config_map = config_map.lock().unwrap();
-
-        if !config_map.contains_key(config_file_path.as_str()) {
-            return Err(Error::new("Configuration file not found"));
-        }
-
-        let config = config_map.get(config_file_path.as_str()).unwrap();
+        let config = registry
+            .lock()
+            .unwrap()
+            .config_by_path(config_file_path)
+            .cloned()
+            .ok_or(Error::new("Configuration file not found"))?;
 
         if id.is_none() {
-            for service in &config.services {
-                cmd_tx
-                    .send(SuperviseurCommand::Stop(
-                        service.clone(),
-                        config.project.clone(),
-                    ))
-                    .unwrap();
-            }
+            let outcomes = send_and_wait(cmd_tx, |reply| {
+                SuperviseurCommand::StopAll(config.project.clone(), Some(reply))
+            })
+            .await?;
             let services = config.services.clone();
             let services = services.iter().map(Service::from).collect::<Vec<Service>>();
             SimpleBroker::publish(AllServicesStopped { payload: services });
+            check_outcomes(outcomes)?;
             return Ok(Process {
                 ..Default::default()
             });
@@ -256,16 +188,15 @@ impl ControlMutation {
             .find(|s| s.id == id.as_ref().map(|x| x.to_string()))
             .ok_or(Error::new("Service not found"))?;
 
-        cmd_tx.send(SuperviseurCommand::Stop(
-            service.clone(),
-            config.project.clone(),
-        ))?;
+        let outcome = send_and_wait(cmd_tx, |reply| {
+            SuperviseurCommand::Stop(service.clone(), config.project.clone(), Some(reply))
+        })
+        .await?;
+        check_outcomes(vec![(service.name.clone(), outcome)])?;
 
-        thread::sleep(Duration::from_secs(1));
-        let processes = processes.lock().unwrap();
-        let (process, _) = processes
-            .iter()
-            .find(|(p, _)| Some(p.service_id.clone()) == service.id)
+        let registry = registry.lock().unwrap();
+        let process = registry
+            .process(&config.project, &service.id.clone().unwrap_or_default())
             .ok_or(Error::new("Process not found"))?;
 
         Ok(Process::from(process.clone()))
@@ -276,35 +207,26 @@ impl ControlMutation {
         let cmd_tx = ctx
             .data::<mpsc::UnboundedSender<SuperviseurCommand>>()
             .unwrap();
-        let processes = ctx
-            .data::<Arc<Mutex<Vec<(types::process::Process, String)>>>>()
-            .unwrap();
-        let config_map = ctx
-            .data::<Arc<Mutex<HashMap<String, ConfigurationData>>>>()
-            .unwrap();
-
-        let config_map = config_map.lock().unwrap();
+        let registry = ctx.data::<Arc<Mutex<Registry>>>().unwrap();
 
-        if !config_map.contains_key(config_file_path.as_str()) {
-            return Err(Error::new("Configuration file not found"));
-        }
-
-        let config = config_map.get(config_file_path.as_str()).unwrap();
+        let config = registry
+            .lock()
+            .unwrap()
+            .config_by_path(config_file_path)
+            .cloned()
+            .ok_or(Error::new("Configuration file not found"))?;
 
         if id.is_none() {
-            for service in &config.services {
-                cmd_tx
-                    .send(SuperviseurCommand::Restart(
-                        service.clone(),
-                        config.project.clone(),
-                    ))
-                    .unwrap();
-            }
+            let outcomes = send_and_wait(cmd_tx, |reply| {
+                SuperviseurCommand::RestartAll(config.project.clone(), Some(reply))
+            })
+            .await?;
 
             let services = config.services.clone();
             let services = services.iter().map(Service::from).collect::<Vec<Service>>();
             SimpleBroker::publish(AllServicesStarted { payload: services });
 
+            check_outcomes(outcomes)?;
             return Ok(Process {
                 ..Default::default()
             });
@@ -316,16 +238,15 @@ impl ControlMutation {
             .find(|s| s.id == id.as_ref().map(|x| x.to_string()))
             .ok_or(Error::new("Service not found"))?;
 
-        cmd_tx.send(SuperviseurCommand::Restart(
-            service.clone(),
-            config.project.clone(),
-        ))?;
+        let outcome = send_and_wait(cmd_tx, |reply| {
+            SuperviseurCommand::Restart(service.clone(), config.project.clone(), Some(reply))
+        })
+        .await?;
+        check_outcomes(vec![(service.name.clone(), outcome)])?;
 
-        thread::sleep(Duration::from_secs(1));
-        let processes = processes.lock().unwrap();
-        let (process, _) = processes
-            .iter()
-            .find(|(p, _)| Some(p.service_id.clone()) == service.id)
+        let registry = registry.lock().unwrap();
+        let process = registry
+            .process(&config.project, &service.id.clone().unwrap_or_default())
             .ok_or(Error::new("Process not found"))?;
 
         Ok(Process::from(process.clone()))
@@ -339,20 +260,13 @@ impl ControlMutation {
         value: String,
     ) -> Result<Service, Error> {
         let config_file_path = ctx.data::<String>().unwrap();
-        let processes = ctx
-            .data::<Arc<Mutex<Vec<(types::process::Process, String)>>>>()
-            .unwrap();
-        let config_map = ctx
-            .data::<Arc<Mutex<HashMap<String, ConfigurationData>>>>()
-            .unwrap();
+        let registry = ctx.data::<Arc<Mutex<Registry>>>().unwrap();
+        let mut registry = registry.lock().unwrap();
 
-        let mut config_map = config_map.lock().unwrap();
-
-        if !config_map.contains_key(config_file_path.as_str()) {
-            return Err(Error::new("Configuration file not found"));
-        }
-
-        let config = config_map.get_mut(config_file_path.as_str()).unwrap();
+        let config = registry
+            .config_by_path_mut(config_file_path)
+            .ok_or(Error::new("Configuration file not found"))?;
+        let project = config.project.clone();
 
         let service = config
             .services
@@ -361,15 +275,14 @@ impl ControlMutation {
             .ok_or(Error::new("Service not found"))?;
 
         service.env.insert(name, value);
-        let processes = processes.lock().unwrap();
-        let (process, _) = processes
-            .iter()
-            .find(|(p, _)| Some(p.service_id.clone()) == service.id)
+        let service = service.clone();
+        let process = registry
+            .process(&project, &id)
             .ok_or(Error::new("Process not found"))?;
 
         Ok(Service {
             status: process.state.to_string(),
-            ..Service::from(service)
+            ..Service::from(&service)
         })
     }
 
@@ -380,20 +293,13 @@ impl ControlMutation {
         name: String,
     ) -> Result<Service, Error> {
         let config_file_path = ctx.data::<String>().unwrap();
-        let processes = ctx
-            .data::<Arc<Mutex<Vec<(types::process::Process, String)>>>>()
-            .unwrap();
-        let config_map = ctx
-            .data::<Arc<Mutex<HashMap<String, ConfigurationData>>>>()
-            .unwrap();
-
-        let mut config_map = config_map.lock().unwrap();
+        let registry = ctx.data::<Arc<Mutex<Registry>>>().unwrap();
+        let mut registry = registry.lock().unwrap();
 
-        if !config_map.contains_key(config_file_path.as_str()) {
-            return Err(Error::new("Configuration file not found"));
-        }
-
-        let config = config_map.get_mut(config_file_path.as_str()).unwrap();
+        let config = registry
+            .config_by_path_mut(config_file_path)
+            .ok_or(Error::new("Configuration file not found"))?;
+        let project = config.project.clone();
 
         let service = config
             .services
@@ -403,15 +309,14 @@ impl ControlMutation {
 
         service.env.remove(&name);
 
-        let processes = processes.lock().unwrap();
-        let (process, _) = processes
-            .iter()
-            .find(|(p, _)| Some(p.service_id.clone()) == service.id)
+        let service = service.clone();
+        let process = registry
+            .process(&project, &id)
             .ok_or(Error::new("Process not found"))?;
 
         Ok(Service {
             status: process.state.to_string(),
-            ..Service::from(service)
+            ..Service::from(&service)
         })
     }
 
@@ -423,20 +328,13 @@ impl ControlMutation {
         value: String,
     ) -> Result<Service, Error> {
         let config_file_path = ctx.data::<String>().unwrap();
-        let processes = ctx
-            .data::<Arc<Mutex<Vec<(types::process::Process, String)>>>>()
-            .unwrap();
-        let config_map = ctx
-            .data::<Arc<Mutex<HashMap<String, ConfigurationData>>>>()
-            .unwrap();
-
-        let mut config_map = config_map.lock().unwrap();
-
-        if !config_map.contains_key(config_file_path.as_str()) {
-            return Err(Error::new("Configuration file not found"));
-        }
+        let registry = ctx.data::<Arc<Mutex<Registry>>>().unwrap();
+        let mut registry = registry.lock().unwrap();
 
-        let config = config_map.get_mut(config_file_path.as_str()).unwrap();
+        let config = registry
+            .config_by_path_mut(config_file_path)
+            .ok_or(Error::new("Configuration file not found"))?;
+        let project = config.project.clone();
 
         let service = config
             .services
@@ -446,19 +344,33 @@ impl ControlMutation {
 
         service.env.remove(&name);
         service.env.insert(name, value);
-        let processes = processes.lock().unwrap();
-        let (process, _) = processes
-            .iter()
-            .find(|(p, _)| Some(p.service_id.clone()) == service.id)
+        let service = service.clone();
+        let process = registry
+            .process(&project, &id)
             .ok_or(Error::new("Process not found"))?;
 
         Ok(Service {
             status: process.state.to_string(),
-            ..Service::from(service)
+            ..Service::from(&service)
         })
     }
 }
 
+// fails with the errors of the services that failed, one per line
+fn check_outcomes(outcomes: Vec<(String, CommandOutcome)>) -> Result<(), Error> {
+    let errors = outcomes
+        .into_iter()
+        .filter_map(|(name, outcome)| match outcome {
+            CommandOutcome::Failed(error) => Some(format!("{}: {}", name, error)),
+            _ => None,
+        })
+        .collect::<Vec<String>>();
+    match errors.is_empty() {
+        true => Ok(()),
+        false => Err(Error::new(errors.join("\n"))),
+    }
+}
+
 #[derive(Default, Clone)]
 pub struct ControlSubscription;
 
@@ -476,6 +388,13 @@ impl ControlSubscription {
         SimpleBroker::<ServiceRestarted>::subscribe()
     }
 
+    async fn on_health_change(
+        &self,
+        _ctx: &Context<'_>,
+    ) -> impl Stream<Item = ServiceHealthChanged> {
+        SimpleBroker::<ServiceHealthChanged>::subscribe()
+    }
+
     async fn on_start_all(&self, _ctx: &Context<'_>) -> impl Stream<Item = AllServicesStarted> {
         SimpleBroker::<AllServicesStarted>::subscribe()
     }
@@ -1,7 +1,4 @@
 use std::{
-    collections::HashMap,
-    fs::File,
-    io::{BufRead, BufReader},
     sync::{Arc, Mutex},
     thread,
     time::Duration,
@@ -13,10 +10,18 @@ use tokio_stream::StreamExt;
 
 use crate::{
     graphql::{schema::objects::subscriptions::TailLogStream, simple_broker::SimpleBroker},
-    types::configuration::ConfigurationData,
+    logs::{self, LogFilter, OutputStream},
+    registry::Registry,
+    types::configuration::Service,
 };
 
-use super::objects::{log::Log, subscriptions};
+use super::objects::{
+    log::{Log, LogSearch},
+    subscriptions,
+};
+
+// lines returned by the logs query when no limit is given
+const DEFAULT_LOGS_LIMIT: usize = 1000;
 
 #[derive(Default, Clone)]
 pub struct LoggingQuery;
@@ -28,74 +33,119 @@ impl LoggingQuery {
         ctx: &Context<'_>,
         id: ID,
         num_lines: Option<usize>,
+        stream: Option<String>,
+        search: Option<LogSearch>,
     ) -> Result<Log, Error> {
-        let config_file_path = ctx.data::<String>().unwrap();
-        let config_map = ctx
-            .data::<Arc<Mutex<HashMap<String, ConfigurationData>>>>()
-            .unwrap();
-
-        let config_map = config_map.lock().unwrap();
-
-        if !config_map.contains_key(config_file_path.as_str()) {
-            return Err(Error::new("Config file not found"));
-        }
-
-        let config = config_map.get(config_file_path.as_str()).unwrap();
-
-        let service = config
-            .services
-            .iter()
-            .find(|s| s.id == Some(id.to_string()))
-            .ok_or_else(|| Error::new("Service not found"))?;
-
-        let log_file = File::open(&service.stdout).map_err(|e| Error::new(e.to_string()))?;
-
-        let reader = BufReader::new(log_file);
-
-        // Read the last `num_lines` lines of the file
-        let mut lines: Vec<String> = reader
-            .lines()
-            .map(|line| line.unwrap())
-            .collect::<Vec<String>>();
-
-        let num_lines = num_lines.unwrap_or(10);
-        let tail_lines = lines.split_off(lines.len().saturating_sub(num_lines));
-
-        Ok(Log { lines: tail_lines })
+        let service = find_service(ctx, &id)?;
+        let filter = parse_filter(stream, search.unwrap_or_default())?;
+
+        let entries = logs::read_stream(&service, &filter, Some(num_lines.unwrap_or(10)))
+            .map_err(|e| Error::new(e.to_string()))?;
+
+        Ok(Log {
+            total: entries.len(),
+            bytes: entries.iter().map(|entry| entry.bytes.clone()).collect(),
+            lines: entries.into_iter().map(|entry| entry.line).collect(),
+            next_offset: None,
+        })
     }
 
-    async fn logs(&self, ctx: &Context<'_>, id: ID) -> Result<Log, Error> {
-        let config_file_path = ctx.data::<String>().unwrap();
-        let config_map = ctx
-            .data::<Arc<Mutex<HashMap<String, ConfigurationData>>>>()
-            .unwrap();
-
-        let config_map = config_map.lock().unwrap();
-
-        if !config_map.contains_key(config_file_path.as_str()) {
-            return Err(Error::new("Config file not found"));
+    /// The lines of the log from `offset`, `limit` at most, `nextOffset` being the
+    /// offset of the next page if there is one.
+    async fn logs(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+        stream: Option<String>,
+        search: Option<LogSearch>,
+        offset: Option<usize>,
+        limit: Option<usize>,
+    ) -> Result<Log, Error> {
+        let service = find_service(ctx, &id)?;
+        let filter = parse_filter(stream, search.unwrap_or_default())?;
+
+        let offset = offset.unwrap_or(0);
+        let limit = limit.unwrap_or(DEFAULT_LOGS_LIMIT);
+        let end = offset.saturating_add(limit);
+        let mut entries = logs::stream_entries(&service, &filter);
+        let mut read = 0;
+        let mut page = vec![];
+        for entry in entries.by_ref().take(end) {
+            let entry = entry.map_err(|e| Error::new(e.to_string()))?;
+            if read >= offset {
+                page.push(entry);
+            }
+            read += 1;
         }
+        let (lines, bytes): (Vec<_>, Vec<_>) = page
+            .into_iter()
+            .map(|entry| (entry.line, entry.bytes))
+            .unzip();
+
+        // the lines after the page are read only to be counted
+        let (next_offset, total) = match ctx.look_ahead().field("total").exists() {
+            true => {
+                let rest = entries
+                    .try_fold(0, |count, entry| entry.map(|_| count + 1))
+                    .map_err(|e| Error::new(e.to_string()))?;
+                ((rest > 0).then_some(end), read + rest)
+            }
+            false => {
+                let next = entries
+                    .next()
+                    .transpose()
+                    .map_err(|e| Error::new(e.to_string()))?;
+                (next.map(|_| end), 0)
+            }
+        };
+
+        Ok(Log {
+            lines,
+            bytes,
+            total,
+            next_offset,
+        })
+    }
+}
 
-        let config = config_map.get(config_file_path.as_str()).unwrap();
-
-        let service = config
-            .services
-            .iter()
-            .find(|s| s.id == Some(id.to_string()))
-            .ok_or_else(|| Error::new("Service not found"))?;
-
-        let log_file = File::open(&service.stdout).map_err(|e| Error::new(e.to_string()))?;
-
-        let reader = BufReader::new(log_file);
+fn find_service(ctx: &Context<'_>, id: &ID) -> Result<Service, Error> {
+    let config_file_path = ctx.data::<String>().unwrap();
+    let registry = ctx.data::<Arc<Mutex<Registry>>>().unwrap();
+    let registry = registry.lock().unwrap();
+
+    let config = registry
+        .config_by_path(config_file_path)
+        .ok_or_else(|| Error::new("Config file not found"))?;
+
+    config
+        .services
+        .iter()
+        .find(|s| s.id == Some(id.to_string()))
+        .cloned()
+        .ok_or_else(|| Error::new("Service not found"))
+}
 
-        let mut lines = vec![];
-        for line in reader.lines() {
-            let line = line.map_err(|e| Error::new(e.to_string()))?;
-            lines.push(line);
-        }
+// the lines of stdout when nothing is given
+fn parse_filter(stream: Option<String>, search: LogSearch) -> Result<LogFilter, Error> {
+    let filter = LogFilter {
+        stream: parse_stream(stream)?,
+        ..Default::default()
+    };
+    filter
+        .search(
+            &search.grep.unwrap_or_default(),
+            search.invert_match.unwrap_or(false),
+            &search.level.unwrap_or_default(),
+        )
+        .map_err(|e| Error::new(e.to_string()))
+}
 
-        Ok(Log { lines })
-    }
+// stdout when not given
+fn parse_stream(stream: Option<String>) -> Result<OutputStream, Error> {
+    stream
+        .unwrap_or_default()
+        .parse()
+        .map_err(|e: anyhow::Error| Error::new(e.to_string()))
 }
 
 #[derive(Default, Clone)]
@@ -103,27 +153,40 @@ pub struct LoggingSubscription;
 
 #[Subscription]
 impl LoggingSubscription {
-    async fn tail(&self, _ctx: &Context<'_>, id: ID) -> impl Stream<Item = TailLogStream> {
-        stream! {
+    async fn tail(
+        &self,
+        _ctx: &Context<'_>,
+        id: ID,
+        stream: Option<String>,
+    ) -> Result<impl Stream<Item = TailLogStream>, Error> {
+        let selected = parse_stream(stream)?;
+        Ok(stream! {
             while let Some(log) = SimpleBroker::<TailLogStream>::subscribe().next().await {
-                if ID(log.id.clone()) == id {
+                if ID(log.id.clone()) == id && is_selected(selected, &log.stream) {
                     yield log;
                 }
             }
-        }
+        })
     }
 
     async fn logs(
         &self,
         _ctx: &Context<'_>,
         id: ID,
-    ) -> impl Stream<Item = subscriptions::LogStream> {
-        stream! {
+        stream: Option<String>,
+    ) -> Result<impl Stream<Item = subscriptions::LogStream>, Error> {
+        let selected = parse_stream(stream)?;
+        Ok(stream! {
             while let Some(log) = SimpleBroker::<subscriptions::LogStream>::subscribe().next().await {
-                if ID(log.id.clone()) == id {
+                if ID(log.id.clone()) == id && is_selected(selected, &log.stream) {
                     yield log;
                 }
             }
-        }
+        })
     }
 }
+
+// whether a line published on `stream` is part of the selected output
+fn is_selected(selected: OutputStream, stream: &str) -> bool {
+    selected == OutputStream::Both || selected.to_string() == stream
+}
@@ -1,8 +1,12 @@
-use async_graphql::Object;
+use async_graphql::{InputObject, Object};
 
 #[derive(Default, Clone)]
 pub struct Log {
     pub lines: Vec<String>,
+    // base64, for the lines which aren't valid UTF-8
+    pub bytes: Vec<Option<String>>,
+    pub total: usize, // lines selected, on all the pages
+    pub next_offset: Option<usize>,
 }
 
 #[Object]
@@ -10,4 +14,28 @@ impl Log {
     async fn lines(&self) -> &Vec<String> {
         &self.lines
     }
+
+    /// Exact bytes of each line in base64 when they aren't valid UTF-8, null otherwise.
+    async fn bytes(&self) -> &Vec<Option<String>> {
+        &self.bytes
+    }
+
+    async fn total(&self) -> usize {
+        self.total
+    }
+
+    async fn next_offset(&self) -> Option<usize> {
+        self.next_offset
+    }
+}
+
+/// Selection of the log lines by content.
+#[derive(InputObject, Default, Clone)]
+pub struct LogSearch {
+    /// Only the lines matching this regular expression
+    pub grep: Option<String>,
+    /// Only the lines not matching `grep`
+    pub invert_match: Option<bool>,
+    /// Only the lines of this level or a more severe one: trace, debug, info, warn or error
+    pub level: Option<String>,
 }
@@ -17,7 +17,13 @@ pub struct Process {
     pub auto_restart: bool,
     pub env: Vec<String>,
     pub state: String,
+    pub health: String,
     pub up_time: String,
+    pub exit_code: Option<i32>,
+    pub exit_signal: Option<String>,
+    pub exited_at: Option<String>,
+    pub restarts: u32,
+    pub error: Option<String>,
 }
 
 #[Object]
@@ -74,9 +80,33 @@ impl Process {
         &self.state
     }
 
+    async fn health(&self) -> &str {
+        &self.health
+    }
+
     async fn up_time(&self) -> &str {
         &self.up_time
     }
+
+    async fn exit_code(&self) -> Option<i32> {
+        self.exit_code
+    }
+
+    async fn exit_signal(&self) -> Option<&str> {
+        self.exit_signal.as_deref()
+    }
+
+    async fn exited_at(&self) -> Option<&str> {
+        self.exited_at.as_deref()
+    }
+
+    async fn restarts(&self) -> u32 {
+        self.restarts
+    }
+
+    async fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
 }
 
 impl From<types::process::Process> for Process {
@@ -87,11 +117,17 @@ impl From<types::process::Process> for Process {
             pid: process.pid,
             command: process.command.clone(),
             state: process.state.to_string(),
+            health: process.health.to_string(),
             r#type: process.r#type.to_string(),
             stderr_file: process.stderr.clone(),
             log_file: process.stdout.clone(),
             up_time: process.up_time.map(|t| t.to_rfc3339()).unwrap_or_default(),
             working_directory: process.working_dir.clone(),
+            exit_code: process.exit_code,
+            exit_signal: process.exit_signal.clone(),
+            exited_at: process.exited_at.map(|t| t.to_rfc3339()),
+            restarts: process.restarts,
+            error: process.error.clone(),
             ..Default::default()
         }
     }
@@ -11,6 +11,7 @@ pub struct Service {
     pub namespace: String,
     pub r#type: String,
     pub status: String,
+    pub health: String,
     pub depends_on: Vec<String>,
     pub env: Vec<String>,
     pub auto_restart: bool,
@@ -50,6 +51,10 @@ impl Service {
         &self.status
     }
 
+    async fn health(&self) -> &str {
+        &self.health
+    }
+
     async fn depends_on(&self) -> &Vec<String> {
         &self.depends_on
     }
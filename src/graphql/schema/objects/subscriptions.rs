@@ -5,11 +5,16 @@ use super::service::Service;
 #[derive(Default, Clone)]
 pub struct TailLogStream {
     pub id: String,
+    pub stream: String,
     pub line: String,
 }
 
 #[Object]
 impl TailLogStream {
+    async fn stream(&self) -> &str {
+        &self.stream
+    }
+
     async fn line(&self) -> &str {
         &self.line
     }
@@ -18,11 +23,16 @@ impl TailLogStream {
 #[derive(Default, Clone)]
 pub struct LogStream {
     pub id: String,
+    pub stream: String,
     pub line: String,
 }
 
 #[Object]
 impl LogStream {
+    async fn stream(&self) -> &str {
+        &self.stream
+    }
+
     async fn line(&self) -> &str {
         &self.line
     }
@@ -64,6 +74,18 @@ impl ServiceRestarted {
     }
 }
 
+#[derive(Default, Clone)]
+pub struct ServiceHealthChanged {
+    pub payload: Service,
+}
+
+#[Object]
+impl ServiceHealthChanged {
+    async fn payload(&self) -> &Service {
+        &self.payload
+    }
+}
+
 #[derive(Default, Clone)]
 pub struct AllServicesStarted {
     pub payload: Vec<Service>,
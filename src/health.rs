@@ -0,0 +1,197 @@
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
+    process::Stdio,
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::Error;
+use tokio::sync::mpsc;
+
+use crate::{
+    superviseur::ProcessEvent,
+    types::{
+        configuration::{HealthCheck, Service},
+        process::Health,
+    },
+};
+
+pub struct HealthChecker {}
+
+impl HealthChecker {
+    /// Probes the service every `interval` and sends a `HealthChanged` event each time
+    /// its health changes, until `is_running` returns false.
+    pub fn new(
+        service: Service,
+        project: String,
+        event_tx: mpsc::UnboundedSender<ProcessEvent>,
+        is_running: impl Fn() -> bool + Send + 'static,
+    ) -> Self {
+        tokio::spawn(async move {
+            let healthcheck = match service.healthcheck.clone() {
+                Some(healthcheck) => healthcheck,
+                None => return,
+            };
+            let started_at = Instant::now();
+            let mut health = Health::Starting;
+            let mut failures = 0;
+
+            loop {
+                tokio::time::sleep(healthcheck.get_interval()).await;
+                if !is_running() {
+                    return;
+                }
+
+                // the probes block, run them off the engine's threads
+                let (s, h) = (service.clone(), healthcheck.clone());
+                let result = match tokio::task::spawn_blocking(move || probe(&s, &h)).await {
+                    Ok(result) => result,
+                    Err(e) => Err(e.into()),
+                };
+                let next = match result {
+                    Ok(()) => {
+                        failures = 0;
+                        Health::Healthy
+                    }
+                    // failures don't count while the service is still starting
+                    Err(_) if started_at.elapsed() < healthcheck.get_start_period() => {
+                        health.clone()
+                    }
+                    Err(e) => {
+                        failures += 1;
+                        if failures < healthcheck.get_retries() {
+                            health.clone()
+                        } else {
+                            if health != Health::Unhealthy {
+                                println!("{} healthcheck failed: {}", service.name, e);
+                            }
+                            Health::Unhealthy
+                        }
+                    }
+                };
+
+                if next != health && is_running() {
+                    health = next;
+                    let event = ProcessEvent::HealthChanged(
+                        Box::new(service.clone()),
+                        project.clone(),
+                        health.clone(),
+                    );
+                    if event_tx.send(event).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+        Self {}
+    }
+}
+
+fn probe(service: &Service, healthcheck: &HealthCheck) -> Result<(), Error> {
+    let timeout = healthcheck.get_timeout();
+    if let Some(command) = &healthcheck.command {
+        return probe_command(service, command, timeout);
+    }
+    if let Some(url) = &healthcheck.http {
+        return probe_http(url, timeout);
+    }
+    match &healthcheck.tcp {
+        Some(address) if address.parse::<u16>().is_ok() => {
+            probe_tcp(&format!("127.0.0.1:{}", address), timeout)
+        }
+        Some(address) => probe_tcp(address, timeout),
+        // no probe given, check that the service accepts connections on its port
+        None => probe_tcp(&format!("127.0.0.1:{}", service.port), timeout),
+    }
+}
+
+fn probe_command(service: &Service, command: &str, timeout: Duration) -> Result<(), Error> {
+    let mut child = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .current_dir(&service.working_dir)
+        .envs(service.env.clone())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            if status.success() {
+                return Ok(());
+            }
+            return Err(Error::msg(format!("`{}` exited with {}", command, status)));
+        }
+        if Instant::now() >= deadline {
+            child.kill()?;
+            child.wait()?;
+            return Err(Error::msg(format!("`{}` timed out", command)));
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+fn connect(address: &str, timeout: Duration) -> Result<TcpStream, Error> {
+    let addresses: Vec<SocketAddr> = address.to_socket_addrs()?.collect();
+    let mut last_error = Error::msg(format!("Cannot resolve {}", address));
+    for addr in addresses {
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = e.into(),
+        }
+    }
+    Err(last_error)
+}
+
+fn probe_tcp(address: &str, timeout: Duration) -> Result<(), Error> {
+    connect(address, timeout)?;
+    Ok(())
+}
+
+fn probe_http(url: &str, timeout: Duration) -> Result<(), Error> {
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or_else(|| Error::msg(format!("Invalid healthcheck url: {}", url)))?;
+    let (host, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    let address = match host.contains(':') {
+        true => host.to_string(),
+        false => format!("{}:80", host),
+    };
+
+    let mut stream = connect(&address, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    stream.write_all(
+        format!(
+            "GET {} HTTP/1.0\r\nHost: {}\r\nConnection: close\r\n\r\n",
+            path, host
+        )
+        .as_bytes(),
+    )?;
+
+    // only the status line is needed, e.g. `HTTP/1.1 200 OK`
+    let mut buf = [0; 32];
+    let mut len = 0;
+    while len < buf.len() {
+        match stream.read(&mut buf[len..])? {
+            0 => break,
+            n => len += n,
+        }
+    }
+    let status_line = String::from_utf8_lossy(&buf[..len]);
+    let status = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| Error::msg(format!("Invalid response from {}", url)))?;
+    match status {
+        200..=399 => Ok(()),
+        _ => Err(Error::msg(format!("{} returned {}", url, status))),
+    }
+}
@@ -0,0 +1,83 @@
+use std::collections::HashSet;
+
+use anyhow::Error;
+use nix::{
+    sys::{
+        signal::Signal,
+        wait::{waitpid, WaitPidFlag},
+    },
+    unistd::{getpgid, Pid},
+};
+use sysinfo::{PidExt, ProcessExt, ProcessStatus, System, SystemExt};
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    sync::mpsc,
+};
+
+use crate::superviseur::SuperviseurCommand;
+
+// signals of the daemon forwarded to the process groups of the services,
+// SIGINT and SIGTERM shut the daemon down instead
+const FORWARDED_SIGNALS: [Signal; 4] = [
+    Signal::SIGHUP,
+    Signal::SIGQUIT,
+    Signal::SIGUSR1,
+    Signal::SIGUSR2,
+];
+
+/// Whether the daemon is the init process of its container, in which case it
+/// inherits the orphaned processes and has to reap them.
+pub fn is_pid1() -> bool {
+    std::process::id() == 1
+}
+
+/// Forwards the signals received by the daemon to the services, and has the
+/// orphans reaped by the engine when the daemon runs as PID 1.
+pub fn handle_signals(cmd_tx: mpsc::UnboundedSender<SuperviseurCommand>) -> Result<(), Error> {
+    for forwarded in FORWARDED_SIGNALS {
+        let mut stream = signal(SignalKind::from_raw(forwarded as i32))?;
+        let cmd_tx = cmd_tx.clone();
+        tokio::spawn(async move {
+            while stream.recv().await.is_some() {
+                cmd_tx.send(SuperviseurCommand::Signal(forwarded)).unwrap();
+            }
+        });
+    }
+
+    if is_pid1() {
+        let mut stream = signal(SignalKind::child())?;
+        tokio::spawn(async move {
+            while stream.recv().await.is_some() {
+                cmd_tx.send(SuperviseurCommand::ReapOrphans).unwrap();
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Reaps the zombies reparented to the daemon. The children spawned by the daemon
+/// are left to the tasks waiting for them: the services, given in `supervised`,
+/// and the health check commands, which are in the process group of the daemon.
+pub fn reap_orphans(supervised: &HashSet<i32>) {
+    let mut system = System::new();
+    system.refresh_processes();
+    let daemon = std::process::id();
+    let daemon_group = getpgid(None).ok();
+
+    for (pid, process) in system.processes() {
+        let pid = pid.as_u32() as i32;
+        if process.parent().map(|p| p.as_u32()) != Some(daemon)
+            || process.status() != ProcessStatus::Zombie
+            || supervised.contains(&pid)
+        {
+            continue;
+        }
+        let pid = Pid::from_raw(pid);
+        if getpgid(Some(pid)).ok() == daemon_group {
+            continue;
+        }
+        if let Err(e) = waitpid(pid, Some(WaitPidFlag::WNOHANG)) {
+            println!("Cannot reap {}: {}", pid, e);
+        }
+    }
+}
@@ -1,7 +1,13 @@
 pub mod cmd;
 pub mod config;
+pub mod graph;
 pub mod graphql;
+pub mod health;
+pub mod init;
+pub mod logs;
+pub mod registry;
 pub mod server;
+pub mod state;
 pub mod superviseur;
 pub mod types;
 pub mod watch;
@@ -9,8 +15,31 @@ pub mod webui;
 pub mod api {
     #[path = ""]
     pub mod superviseur {
+        use crate::superviseur::CommandOutcome;
+
+        use self::v1alpha1::ServiceOutcome;
+
         #[path = "superviseur.v1alpha1.rs"]
         pub mod v1alpha1;
+
+        impl From<(String, CommandOutcome)> for ServiceOutcome {
+            fn from((service, outcome): (String, CommandOutcome)) -> Self {
+                let (outcome, pid, error) = match outcome {
+                    CommandOutcome::Started(pid) => ("started", pid, String::new()),
+                    CommandOutcome::AlreadyRunning(pid) => ("already_running", pid, String::new()),
+                    CommandOutcome::Stopped => ("stopped", 0, String::new()),
+                    CommandOutcome::NotRunning => ("not_running", 0, String::new()),
+                    CommandOutcome::Loaded => ("loaded", 0, String::new()),
+                    CommandOutcome::Failed(error) => ("failed", 0, error),
+                };
+                Self {
+                    service,
+                    outcome: outcome.to_string(),
+                    pid,
+                    error,
+                }
+            }
+        }
     }
     #[path = ""]
     pub mod objects {
@@ -42,7 +71,7 @@ pub mod api {
                 Self {
                     id: service.id.unwrap_or_default(),
                     name: service.name,
-                    depends_on: service.depends_on,
+                    depends_on: service.depends_on.names(),
                     command: service.command,
                     r#type: service.r#type,
                     ..Default::default()
@@ -76,6 +105,12 @@ pub mod api {
                     auto_restart: self.auto_restart,
                     env,
                     service_id: self.service_id,
+                    exit_code: self.exit_code,
+                    exit_signal: Some(self.exit_signal).filter(|s| !s.is_empty()),
+                    exited_at: self.exited_at.parse().ok(),
+                    restarts: self.restarts,
+                    health: self.health.parse().unwrap_or_default(),
+                    error: Some(self.error).filter(|e| !e.is_empty()),
                     ..Default::default()
                 }
             }
@@ -102,6 +137,15 @@ pub mod api {
                     auto_restart: process.auto_restart,
                     env,
                     service_id: process.service_id,
+                    exit_code: process.exit_code,
+                    exit_signal: process.exit_signal.unwrap_or_default(),
+                    exited_at: process
+                        .exited_at
+                        .map(|t| t.to_rfc3339())
+                        .unwrap_or_default(),
+                    restarts: process.restarts,
+                    health: process.health.to_string(),
+                    error: process.error.unwrap_or_default(),
                     ..Default::default()
                 }
             }
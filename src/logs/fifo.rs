@@ -0,0 +1,44 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, ErrorKind},
+    path::PathBuf,
+};
+
+use nix::{sys::stat::Mode, unistd::mkfifo};
+use tokio::net::unix::pipe::{self, Receiver};
+
+use super::OutputStream;
+
+/// Directory of the named pipes through which the services write their output, next
+/// to the socket of the daemon.
+pub const FIFO_DIR: &str = "/tmp/superviseur";
+
+/// Named pipe through which a service writes one of its output streams. Unlike an
+/// anonymous pipe, it outlives the daemon, so that the next daemon can read it again.
+pub fn path(service_id: &str, stream: OutputStream) -> PathBuf {
+    PathBuf::from(FIFO_DIR).join(format!("{}.{}", service_id, stream))
+}
+
+/// Creates the named pipe of an output stream of a service, replacing the one of its
+/// previous run, and opens both of its ends. The end given to the service is opened
+/// for reading as well: a pipe which still has a reader never raises `SIGPIPE`, so the
+/// service keeps running while no daemon reads its output, and only blocks once the
+/// pipe is full.
+pub fn create(service_id: &str, stream: OutputStream) -> io::Result<(Receiver, File)> {
+    fs::create_dir_all(FIFO_DIR)?;
+    let path = path(service_id, stream);
+    match fs::remove_file(&path) {
+        Err(e) if e.kind() != ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    mkfifo(&path, Mode::S_IRUSR | Mode::S_IWUSR)?;
+    let receiver = pipe::OpenOptions::new().open_receiver(&path)?;
+    let sender = OpenOptions::new().read(true).write(true).open(&path)?;
+    Ok((receiver, sender))
+}
+
+/// Opens again the named pipe of an output stream of a service spawned by a previous
+/// daemon.
+pub fn open(service_id: &str, stream: OutputStream) -> io::Result<Receiver> {
+    pipe::OpenOptions::new().open_receiver(path(service_id, stream))
+}
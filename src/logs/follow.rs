@@ -0,0 +1,365 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{Read, Seek, SeekFrom},
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use notify::{Config, Event, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+
+use super::{Joiner, LogEntry, LogFilter, OutputStream, ReadPosition};
+
+// in case a change of the file is not notified
+const RESCAN_INTERVAL: Duration = Duration::from_secs(1);
+
+// time a merged line is held after it was received, for the lines of the other files
+// received before it but read after it to be sent first
+const REORDER_WINDOW: Duration = Duration::from_millis(300);
+
+/// Sends the selected lines written to a log file from where the follower starts,
+/// until the receiver is dropped, as the responses of a stream which may also carry
+/// errors. The file is read again from its start when it is truncated, and once it has
+/// been rotated, the end of the rotated file is read before the new one.
+pub async fn follow<T, E>(
+    mut follower: LogFollower,
+    stream: OutputStream,
+    filter: LogFilter,
+    tx: mpsc::Sender<Result<T, E>>,
+    response: impl Fn(LogEntry) -> T,
+) {
+    let path = follower.path.clone();
+    let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+    let watched = PathBuf::from(&path);
+    let handler = move |result: notify::Result<Event>| {
+        if let Ok(event) = result {
+            if event.paths.iter().any(|p| p == &watched) {
+                let _ = event_tx.send(());
+            }
+        }
+    };
+    // the directory is watched, to be notified when the file is created again
+    let directory = match Path::new(&path).parent() {
+        Some(parent) if parent != Path::new("") => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let watcher = RecommendedWatcher::new(handler, Config::default()).and_then(|mut watcher| {
+        watcher.watch(&directory, RecursiveMode::NonRecursive)?;
+        Ok(watcher)
+    });
+    if let Err(e) = &watcher {
+        println!("Cannot watch {}: {}", path, e);
+    }
+
+    // a split line is sent once all its parts have been written
+    let mut joiner = Joiner::default();
+    loop {
+        for line in follower.read_lines() {
+            joiner.push(LogEntry::parse(&line, stream));
+        }
+        while let Some(entry) = joiner.pop() {
+            if !filter.matches(&entry) {
+                continue;
+            }
+            if tx.send(Ok(response(entry))).await.is_err() {
+                return;
+            }
+        }
+        tokio::select! {
+            _ = tx.closed() => return,
+            _ = event_rx.recv() => {},
+            _ = tokio::time::sleep(RESCAN_INTERVAL) => {},
+        }
+    }
+}
+
+/// Reader of the lines appended to a log file.
+pub struct LogFollower {
+    path: String,
+    file: Option<File>,
+    position: u64,
+    // last line, until it is complete
+    partial: Vec<u8>,
+}
+
+impl LogFollower {
+    /// Starts at the end of the file, or at the start of the file once created.
+    pub fn new(path: &str) -> Self {
+        let file = File::open(path).ok();
+        let position = file
+            .as_ref()
+            .and_then(|file| file.metadata().ok())
+            .map(|metadata| metadata.len())
+            .unwrap_or(0);
+        Self {
+            path: path.to_string(),
+            file,
+            position,
+            partial: vec![],
+        }
+    }
+
+    /// Starts where the file has been read up to, or at the start of the file once
+    /// created if it has not been read.
+    pub fn from(path: &str, position: Option<ReadPosition>) -> Self {
+        let (file, position) = match position {
+            Some(position) => (Some(position.file), position.offset),
+            None => (None, 0),
+        };
+        Self {
+            path: path.to_string(),
+            file,
+            position,
+            partial: vec![],
+        }
+    }
+
+    // complete lines written since the last call
+    fn read_lines(&mut self) -> Vec<String> {
+        let mut lines = vec![];
+        self.read_available(&mut lines);
+
+        // the file has been rotated or removed and created again: the end of the previous
+        // one has just been read through the file still open, the new one is read from
+        // its start
+        if self.is_replaced() {
+            if let Ok(file) = File::open(&self.path) {
+                if !self.partial.is_empty() {
+                    lines.push(String::from_utf8_lossy(&self.partial).to_string());
+                    self.partial.clear();
+                }
+                self.file = Some(file);
+                self.position = 0;
+                self.read_available(&mut lines);
+            }
+        }
+        lines
+    }
+
+    fn read_available(&mut self, lines: &mut Vec<String>) {
+        let file = match self.file.as_mut() {
+            Some(file) => file,
+            None => return,
+        };
+        let len = match file.metadata() {
+            Ok(metadata) => metadata.len(),
+            Err(_) => return,
+        };
+        // the file has been truncated
+        if len < self.position {
+            self.position = 0;
+            self.partial.clear();
+        }
+        if len == self.position {
+            return;
+        }
+
+        let mut content = vec![];
+        let read = file
+            .seek(SeekFrom::Start(self.position))
+            .and_then(|_| file.read_to_end(&mut content));
+        if read.is_err() {
+            return;
+        }
+        self.position += content.len() as u64;
+        self.partial.extend(content);
+
+        while let Some(end) = self.partial.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.partial.drain(..=end).collect();
+            lines.push(String::from_utf8_lossy(&line[..end]).to_string());
+        }
+    }
+
+    // whether the path no longer refers to the file being read
+    fn is_replaced(&self) -> bool {
+        let current = match fs::metadata(&self.path) {
+            Ok(metadata) => metadata,
+            Err(_) => return false,
+        };
+        match self.file.as_ref().and_then(|file| file.metadata().ok()) {
+            Some(read) => read.ino() != current.ino() || read.dev() != current.dev(),
+            None => true,
+        }
+    }
+}
+
+/// Response of a follower along with the time its line was received.
+pub type Timed<T> = (Option<DateTime<Utc>>, T);
+
+/// Sends the responses of several followers in the order their lines were received,
+/// which the followers give along with their responses, until the receiver is dropped.
+/// A line is held until `REORDER_WINDOW` after it was received, so the lines are only
+/// roughly ordered when a file is read later than that, as when its changes are not
+/// notified.
+pub fn merge<T, E>(tx: mpsc::Sender<Result<T, E>>) -> mpsc::Sender<Result<Timed<T>, E>>
+where
+    T: Send + 'static,
+    E: Send + 'static,
+{
+    let (merge_tx, mut rx) = mpsc::channel(1024);
+    tokio::spawn(async move {
+        // by time of reception, then of arrival, the lines without a time being first
+        let mut held: BTreeMap<(Option<DateTime<Utc>>, u64), T> = BTreeMap::new();
+        let mut index: u64 = 0;
+        let mut open = true;
+        loop {
+            let wait = held.keys().next().map(|(timestamp, _)| match timestamp {
+                Some(timestamp) => {
+                    let age = (Utc::now() - *timestamp).to_std().unwrap_or_default();
+                    REORDER_WINDOW.saturating_sub(age)
+                }
+                None => Duration::ZERO,
+            });
+            if !open && wait.is_none() {
+                return;
+            }
+            tokio::select! {
+                _ = tx.closed() => return,
+                line = rx.recv(), if open => match line {
+                    Some(Ok((timestamp, response))) => {
+                        held.insert((timestamp, index), response);
+                        index += 1;
+                    }
+                    Some(Err(e)) => {
+                        if tx.send(Err(e)).await.is_err() {
+                            return;
+                        }
+                    }
+                    // all the followers have stopped
+                    None => open = false,
+                },
+                _ = tokio::time::sleep(wait.unwrap_or_default()), if wait.is_some() => {
+                    if let Some((_, response)) = held.pop_first() {
+                        if tx.send(Ok(response)).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    });
+    merge_tx
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::OpenOptions, io::Write};
+
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::logs::{read_last_entries, Entries};
+
+    fn append(path: &Path, content: &str) {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .unwrap();
+        file.write_all(content.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn reads_the_complete_lines_written_from_now_on() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("out.log");
+        append(&path, "old\n");
+        let mut follower = LogFollower::new(path.to_str().unwrap());
+        assert!(follower.read_lines().is_empty());
+
+        append(&path, "new\npart");
+        assert_eq!(follower.read_lines(), ["new"]);
+        append(&path, "ial\n");
+        assert_eq!(follower.read_lines(), ["partial"]);
+    }
+
+    #[test]
+    fn reads_a_file_created_afterwards_from_its_start() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("out.log");
+        let mut follower = LogFollower::new(path.to_str().unwrap());
+        assert!(follower.read_lines().is_empty());
+
+        append(&path, "first\n");
+        assert_eq!(follower.read_lines(), ["first"]);
+    }
+
+    #[test]
+    fn reads_a_truncated_file_from_its_start() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("out.log");
+        append(&path, "a long line before the truncation\n");
+        let mut follower = LogFollower::new(path.to_str().unwrap());
+
+        fs::write(&path, "again\n").unwrap();
+        assert_eq!(follower.read_lines(), ["again"]);
+    }
+
+    #[test]
+    fn reads_the_end_of_a_rotated_file_before_the_new_one() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("out.log");
+        append(&path, "old\n");
+        let mut follower = LogFollower::new(path.to_str().unwrap());
+
+        append(&path, "last");
+        let rotated = dir.path().join("out.log.1");
+        fs::rename(&path, &rotated).unwrap();
+        append(&rotated, " line\n");
+        append(&path, "first\n");
+        assert_eq!(follower.read_lines(), ["last line", "first"]);
+    }
+
+    #[test]
+    fn reads_the_lines_written_since_the_history_was_read() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("out.log");
+        let path_str = path.to_str().unwrap();
+        append(&path, "old\n");
+        let mut entries = Entries::new(path_str, OutputStream::Stdout, LogFilter::default());
+        let history: Vec<_> = entries.by_ref().map(|entry| entry.unwrap().line).collect();
+        assert_eq!(history, ["old"]);
+
+        append(&path, "between\n");
+        fs::rename(&path, dir.path().join("out.log.1")).unwrap();
+        append(&path, "new\n");
+        let mut follower = LogFollower::from(path_str, entries.into_position());
+        assert_eq!(follower.read_lines(), ["between", "new"]);
+    }
+
+    #[test]
+    fn reads_the_lines_written_since_the_last_lines_were_read() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("out.log");
+        let path_str = path.to_str().unwrap();
+        append(&path, "one\ntwo\n");
+        let (last, position) =
+            read_last_entries(path_str, OutputStream::Stdout, &LogFilter::default(), 1).unwrap();
+        assert_eq!(last[0].line, "two");
+
+        append(&path, "three\n");
+        let mut follower = LogFollower::from(path_str, position);
+        assert_eq!(follower.read_lines(), ["three"]);
+    }
+
+    #[tokio::test]
+    async fn merges_the_lines_by_time_of_reception() {
+        let (tx, mut rx) = mpsc::channel::<Result<&str, ()>>(8);
+        let merged = merge(tx);
+        let now = Utc::now();
+        let earlier = now - chrono::Duration::milliseconds(100);
+        merged.send(Ok((Some(now), "read first"))).await.unwrap();
+        merged
+            .send(Ok((Some(earlier), "received first")))
+            .await
+            .unwrap();
+        drop(merged);
+
+        assert_eq!(rx.recv().await, Some(Ok("received first")));
+        assert_eq!(rx.recv().await, Some(Ok("read first")));
+        assert_eq!(rx.recv().await, None);
+    }
+}
@@ -0,0 +1,1098 @@
+use std::{
+    collections::VecDeque,
+    fmt::Display,
+    fs::File,
+    io::{self, BufRead, Read, Seek, SeekFrom},
+    path::Path,
+    str::FromStr,
+};
+
+use anyhow::Error;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{
+    DateTime, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, SecondsFormat, TimeZone, Utc,
+};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{logs::follow::LogFollower, types::configuration::Service};
+
+pub mod fifo;
+pub mod follow;
+pub mod rotation;
+
+// size of the blocks in which the end of a log file is read
+const READ_CHUNK_SIZE: usize = 8192;
+
+/// Output stream of a service, or both of them, to read the logs of.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputStream {
+    #[default]
+    Stdout,
+    Stderr,
+    Both,
+}
+
+impl FromStr for OutputStream {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "" | "stdout" => Ok(OutputStream::Stdout),
+            "stderr" => Ok(OutputStream::Stderr),
+            "both" => Ok(OutputStream::Both),
+            _ => Err(Error::msg(format!(
+                "Unknown stream {}, expected stdout, stderr or both",
+                s
+            ))),
+        }
+    }
+}
+
+impl Display for OutputStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OutputStream::Stdout => write!(f, "stdout"),
+            OutputStream::Stderr => write!(f, "stderr"),
+            OutputStream::Both => write!(f, "both"),
+        }
+    }
+}
+
+impl OutputStream {
+    /// Log files of the service for this stream, along with the stream each one
+    /// contains. A single file is returned when both streams go to the same file.
+    pub fn files(&self, service: &Service) -> Vec<(OutputStream, String)> {
+        let stdout = (OutputStream::Stdout, service.stdout.clone());
+        let stderr = (OutputStream::Stderr, service.stderr.clone());
+        match self {
+            OutputStream::Stdout => vec![stdout],
+            OutputStream::Stderr => vec![stderr],
+            OutputStream::Both if service.stdout == service.stderr => vec![stdout],
+            OutputStream::Both => vec![stdout, stderr],
+        }
+    }
+}
+
+/// A line written by a service, stored in its log files as a JSON object per line.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LogEntry {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<DateTime<Utc>>, // when the line has been received
+    pub stream: OutputStream,
+    pub service: String, // id of the service
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pid: Option<u32>,
+    pub line: String, // lossily decoded when not valid UTF-8
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<String>, // exact bytes of a line which is not valid UTF-8, in base64
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub partial: bool, // the line goes on in the next entry, having been split
+}
+
+impl LogEntry {
+    /// A line just received from a service, without its newline.
+    pub fn received(
+        bytes: &[u8],
+        partial: bool,
+        stream: OutputStream,
+        service: &str,
+        pid: u32,
+    ) -> Self {
+        let (line, bytes) = decode(bytes);
+        LogEntry {
+            timestamp: Some(Utc::now()),
+            stream,
+            service: service.to_string(),
+            pid: Some(pid),
+            line,
+            bytes,
+            partial,
+        }
+    }
+
+    /// Parses a line of a log file. The lines written before the logs were structured
+    /// are kept as they are, without a timestamp.
+    pub fn parse(raw: &str, stream: OutputStream) -> Self {
+        serde_json::from_str(raw).unwrap_or_else(|_| LogEntry {
+            timestamp: None,
+            stream,
+            service: String::new(),
+            pid: None,
+            line: raw.to_string(),
+            bytes: None,
+            partial: false,
+        })
+    }
+
+    /// Exact bytes of the line, as written by the service.
+    pub fn raw_bytes(&self) -> Vec<u8> {
+        self.bytes
+            .as_ref()
+            .and_then(|bytes| STANDARD.decode(bytes).ok())
+            .unwrap_or_else(|| self.line.as_bytes().to_vec())
+    }
+
+    /// Exact bytes of the line when they differ from `line`, since they aren't valid
+    /// UTF-8, empty otherwise.
+    pub fn invalid_bytes(&self) -> Vec<u8> {
+        match self.bytes {
+            Some(_) => self.raw_bytes(),
+            None => vec![],
+        }
+    }
+
+    // appends the next part of a line which has been split, whose bytes are joined
+    // before being decoded, a character may have been split as well
+    fn append(&mut self, next: LogEntry) {
+        let mut bytes = self.raw_bytes();
+        bytes.extend(next.raw_bytes());
+        (self.line, self.bytes) = decode(&bytes);
+        self.partial = next.partial;
+    }
+
+    // whether the entry is part of the same output as a previous one
+    fn same_output(&self, other: &LogEntry) -> bool {
+        self.service == other.service && self.stream == other.stream
+    }
+
+    /// Receive time of the line as RFC 3339, empty if unknown.
+    pub fn rfc3339(&self) -> String {
+        self.timestamp
+            .map(|t| t.to_rfc3339_opts(SecondsFormat::Millis, true))
+            .unwrap_or_default()
+    }
+}
+
+// the line lossily decoded, along with its bytes in base64 if they aren't valid UTF-8
+fn decode(bytes: &[u8]) -> (String, Option<String>) {
+    match std::str::from_utf8(bytes) {
+        Ok(line) => (line.to_string(), None),
+        Err(_) => (
+            String::from_utf8_lossy(bytes).to_string(),
+            Some(STANDARD.encode(bytes)),
+        ),
+    }
+}
+
+// joins the parts of the lines which have been split, read from the oldest, and gives
+// back the lines in the order of their first part
+#[derive(Default)]
+struct Joiner {
+    // the last ones may be incomplete
+    lines: VecDeque<LogEntry>,
+}
+
+impl Joiner {
+    fn push(&mut self, entry: LogEntry) {
+        let previous = self
+            .lines
+            .iter_mut()
+            .find(|line| line.partial && line.same_output(&entry));
+        match previous {
+            Some(previous) if previous.pid == entry.pid => previous.append(entry),
+            // the end of the line has been lost along with its process
+            Some(previous) => {
+                previous.partial = false;
+                self.lines.push_back(entry);
+            }
+            None => self.lines.push_back(entry),
+        }
+    }
+
+    // the next line once all its parts have been read
+    fn pop(&mut self) -> Option<LogEntry> {
+        match self.lines.front() {
+            Some(line) if !line.partial => self.lines.pop_front(),
+            _ => None,
+        }
+    }
+
+    // the next line at the end of the logs, even if it is incomplete
+    fn flush(&mut self) -> Option<LogEntry> {
+        self.lines.pop_front()
+    }
+}
+
+/// Level of a log line, from the least to the most severe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl FromStr for Level {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "trace" => Ok(Level::Trace),
+            "debug" => Ok(Level::Debug),
+            "info" => Ok(Level::Info),
+            "warn" | "warning" => Ok(Level::Warn),
+            "error" => Ok(Level::Error),
+            _ => Err(Error::msg(format!(
+                "Unknown level {}, expected trace, debug, info, warn or error",
+                s
+            ))),
+        }
+    }
+}
+
+impl Level {
+    /// Level of a line, given by the first word of the line naming a level,
+    /// e.g. `ERROR`, `[warn]` or `"level":"info"`.
+    pub fn detect(line: &str) -> Option<Level> {
+        line.split(|c: char| !c.is_ascii_alphanumeric())
+            .find_map(|word| match word.to_lowercase().as_str() {
+                "trace" => Some(Level::Trace),
+                "debug" | "dbg" => Some(Level::Debug),
+                "info" => Some(Level::Info),
+                "warn" | "warning" => Some(Level::Warn),
+                "error" | "err" | "fatal" | "panic" | "crit" | "critical" => Some(Level::Error),
+                _ => None,
+            })
+    }
+}
+
+/// Selection of the lines to read from the log files of a service.
+#[derive(Debug, Default, Clone)]
+pub struct LogFilter {
+    pub stream: OutputStream,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub pattern: Option<Regex>,
+    pub invert_match: bool, // the lines not matching the pattern are selected instead
+    pub level: Option<Level>, // the lines of this level or a more severe one
+}
+
+impl LogFilter {
+    /// Builds a filter from the values of a request, empty values being ignored.
+    pub fn parse(stream: &str, since: &str, until: &str) -> Result<Self, Error> {
+        Ok(LogFilter {
+            stream: stream.parse()?,
+            since: parse_optional_time(since)?,
+            until: parse_optional_time(until)?,
+            ..Default::default()
+        })
+    }
+
+    /// Also selects the lines by a regular expression and by level, empty values
+    /// being ignored.
+    pub fn search(mut self, pattern: &str, invert_match: bool, level: &str) -> Result<Self, Error> {
+        if !pattern.is_empty() {
+            let regex = Regex::new(pattern)
+                .map_err(|e| Error::msg(format!("Invalid pattern {}: {}", pattern, e)))?;
+            self.pattern = Some(regex);
+        }
+        self.invert_match = invert_match;
+        if !level.is_empty() {
+            self.level = Some(level.parse()?);
+        }
+        Ok(self)
+    }
+
+    /// Whether the line is selected. The lines without a timestamp are left out as
+    /// soon as a time range is given, those without a level as soon as a level is.
+    pub fn matches(&self, entry: &LogEntry) -> bool {
+        if self.stream != OutputStream::Both && entry.stream != self.stream {
+            return false;
+        }
+        if self.since.is_some() || self.until.is_some() {
+            let in_range = match entry.timestamp {
+                Some(timestamp) => {
+                    self.since.map(|since| timestamp >= since).unwrap_or(true)
+                        && self.until.map(|until| timestamp <= until).unwrap_or(true)
+                }
+                None => false,
+            };
+            if !in_range {
+                return false;
+            }
+        }
+        if let Some(pattern) = &self.pattern {
+            if pattern.is_match(&entry.line) == self.invert_match {
+                return false;
+            }
+        }
+        match self.level {
+            Some(level) => Level::detect(&entry.line)
+                .map(|detected| detected >= level)
+                .unwrap_or(false),
+            None => true,
+        }
+    }
+}
+
+fn parse_optional_time(value: &str) -> Result<Option<DateTime<Utc>>, Error> {
+    match value {
+        "" => Ok(None),
+        value => parse_time(value).map(Some),
+    }
+}
+
+/// Parses a point in time given as RFC 3339, as a local date and time (`2023-03-05 14:02`,
+/// `2023-03-05`), as a local time of today (`14:02`), or relatively to now (`30s`,
+/// `10m`, `2h`, `1d` ago).
+pub fn parse_time(value: &str) -> Result<DateTime<Utc>, Error> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"] {
+        if let Ok(time) = NaiveDateTime::parse_from_str(value, format) {
+            return local_to_utc(time, value);
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return local_to_utc(date.and_hms_opt(0, 0, 0).unwrap(), value);
+    }
+    for format in ["%H:%M:%S", "%H:%M"] {
+        if let Ok(time) = NaiveTime::parse_from_str(value, format) {
+            return local_to_utc(Local::now().date_naive().and_time(time), value);
+        }
+    }
+
+    let invalid = || {
+        Error::msg(format!(
+            "Invalid time {}, expected e.g. 2023-03-05T14:02:00Z, 2023-03-05 14:02, 14:02 or 10m",
+            value
+        ))
+    };
+    let (amount, unit) = value.split_at(value.len().saturating_sub(1));
+    let amount: i64 = amount.parse().map_err(|_| invalid())?;
+    let ago = match unit {
+        "s" => Duration::seconds(amount),
+        "m" => Duration::minutes(amount),
+        "h" => Duration::hours(amount),
+        "d" => Duration::days(amount),
+        _ => return Err(invalid()),
+    };
+    Ok(Utc::now() - ago)
+}
+
+fn local_to_utc(time: NaiveDateTime, value: &str) -> Result<DateTime<Utc>, Error> {
+    Local
+        .from_local_datetime(&time)
+        .earliest()
+        .map(|time| time.with_timezone(&Utc))
+        .ok_or_else(|| Error::msg(format!("Invalid local time {}", value)))
+}
+
+/// Reads the selected lines of a log file, preceded by those of its rotated files,
+/// only the `last` ones if given, in which case the older files are read only if needed.
+/// `stream` is the stream of the lines without a stream, written before the logs were
+/// structured. The parts of the lines which have been split are joined.
+pub fn read_entries(
+    path: &str,
+    stream: OutputStream,
+    filter: &LogFilter,
+    last: Option<usize>,
+) -> Result<Vec<LogEntry>, Error> {
+    match last {
+        Some(last) => read_last_entries(path, stream, filter, last).map(|(entries, _)| entries),
+        None => Entries::new(path, stream, filter.clone()).collect(),
+    }
+}
+
+/// Selected lines of a log file and of its rotated files, from the oldest, read as
+/// they are iterated.
+pub struct Entries {
+    lines: ForwardLines,
+    stream: OutputStream,
+    filter: LogFilter,
+    joiner: Joiner,
+}
+
+impl Entries {
+    pub fn new(path: &str, stream: OutputStream, filter: LogFilter) -> Self {
+        let mut files = rotation::rotated_files(path);
+        files.reverse();
+        files.push(path.to_string());
+        Self {
+            lines: ForwardLines {
+                files: files.into(),
+                reader: None,
+                position: None,
+            },
+            stream,
+            filter,
+            joiner: Joiner::default(),
+        }
+    }
+
+    /// Where the most recent file has been read up to, once all the lines are read.
+    pub fn into_position(self) -> Option<ReadPosition> {
+        self.lines.position
+    }
+}
+
+/// Where a log file has been read up to: the file which was read, even if it has been
+/// rotated since, and the offset of the end of the lines read.
+pub struct ReadPosition {
+    pub file: File,
+    pub offset: u64,
+}
+
+impl Iterator for Entries {
+    type Item = Result<LogEntry, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let entry = match self.joiner.pop() {
+                Some(entry) => entry,
+                None => match self.lines.next() {
+                    Some(Ok(line)) => {
+                        let entry = LogEntry::parse(&String::from_utf8_lossy(&line), self.stream);
+                        self.joiner.push(entry);
+                        continue;
+                    }
+                    Some(Err(e)) => return Some(Err(e.into())),
+                    None => self.joiner.flush()?,
+                },
+            };
+            if self.filter.matches(&entry) {
+                return Some(Ok(entry));
+            }
+        }
+    }
+}
+
+// lines of log files, read one file after the other
+struct ForwardLines {
+    files: VecDeque<String>,
+    reader: Option<Box<dyn BufRead>>,
+    // in the file being read, unless it is compressed
+    position: Option<ReadPosition>,
+}
+
+impl Iterator for ForwardLines {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let reader = match self.reader.as_mut() {
+                Some(reader) => reader,
+                None => {
+                    let (path, file) = match rotation::open(&self.files.pop_front()?) {
+                        Ok(opened) => opened,
+                        Err(e) => return Some(Err(e)),
+                    };
+                    self.position = match path.ends_with(".gz") {
+                        true => None,
+                        false => file
+                            .try_clone()
+                            .ok()
+                            .map(|file| ReadPosition { file, offset: 0 }),
+                    };
+                    self.reader.insert(rotation::reader(&path, file))
+                }
+            };
+            let mut line = vec![];
+            match reader.read_until(b'\n', &mut line) {
+                Ok(0) => self.reader = None,
+                Ok(read) => {
+                    if let Some(position) = self.position.as_mut() {
+                        position.offset += read as u64;
+                    }
+                    if line.ends_with(b"\n") {
+                        line.pop();
+                    }
+                    return Some(Ok(line));
+                }
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+// reads the log files from the end of the most recent one, until the `last` selected
+// lines are found, and gives the end of the most recent one as well
+fn read_last_entries(
+    path: &str,
+    stream: OutputStream,
+    filter: &LogFilter,
+    last: usize,
+) -> Result<(Vec<LogEntry>, Option<ReadPosition>), Error> {
+    let file = FileLines::open(path)?;
+    let position = file.end();
+    let mut lines = BackwardLines {
+        files: rotation::rotated_files(path).into(),
+        file: Some(file),
+    };
+    // the most recent first
+    let mut entries: Vec<LogEntry> = vec![];
+    // last part read of the lines which may have been split, of each output: their
+    // lines are complete once a line of the same output which goes on in the next
+    // entry is read
+    let mut tails: Vec<LogEntry> = vec![];
+    let select = |entry: LogEntry, entries: &mut Vec<LogEntry>| {
+        if filter.matches(&entry) {
+            entries.push(entry);
+        }
+    };
+
+    while entries.len() < last || !tails.is_empty() {
+        if entries.len() >= last {
+            // the lines still to be completed are selected too if they were received
+            // after the oldest selected one
+            entries.sort_by_key(|entry| std::cmp::Reverse(entry.timestamp));
+            entries.truncate(last);
+            let oldest = entries.last().and_then(|entry| entry.timestamp);
+            tails.retain(|tail| tail.timestamp >= oldest);
+            if tails.is_empty() {
+                break;
+            }
+        }
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => {
+                for tail in tails.drain(..) {
+                    select(tail, &mut entries);
+                }
+                break;
+            }
+        };
+        if line.is_empty() {
+            continue;
+        }
+        let mut entry = LogEntry::parse(&String::from_utf8_lossy(&line), stream);
+        if let Some(index) = tails.iter().position(|tail| tail.same_output(&entry)) {
+            let tail = tails.remove(index);
+            if entry.partial && entry.pid == tail.pid {
+                entry.append(tail);
+            } else {
+                select(tail, &mut entries);
+            }
+        }
+        tails.push(entry);
+    }
+
+    // the lines of different outputs are completed in a different order
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.timestamp));
+    entries.truncate(last);
+    entries.reverse();
+    Ok((entries, position))
+}
+
+// lines of log files from the end of the most recent one
+struct BackwardLines {
+    files: VecDeque<String>,
+    file: Option<FileLines>,
+}
+
+impl Iterator for BackwardLines {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let file = match self.file.as_mut() {
+                Some(file) => file,
+                None => {
+                    let path = self.files.pop_front()?;
+                    match FileLines::open(&path) {
+                        Ok(file) => self.file.insert(file),
+                        Err(e) => return Some(Err(e)),
+                    }
+                }
+            };
+            match file.previous() {
+                Ok(Some(line)) => return Some(Ok(line)),
+                Ok(None) => self.file = None,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+// lines of a file read from its end: by chunks, unless it is compressed, in which
+// case it is read from its start at once
+enum FileLines {
+    Plain {
+        file: File,
+        position: u64,
+        // start of the file read so far, up to its first newline
+        head: Vec<u8>,
+        // lines of the chunks read, not returned yet
+        lines: Vec<Vec<u8>>,
+    },
+    Compressed(Vec<Vec<u8>>),
+}
+
+impl FileLines {
+    fn open(path: &str) -> io::Result<Self> {
+        let (path, mut file) = rotation::open(path)?;
+        if path.ends_with(".gz") {
+            let lines = rotation::reader(&path, file)
+                .split(b'\n')
+                .collect::<io::Result<_>>()?;
+            return Ok(FileLines::Compressed(lines));
+        }
+        let position = file.seek(SeekFrom::End(0))?;
+        Ok(FileLines::Plain {
+            file,
+            position,
+            head: vec![],
+            lines: vec![],
+        })
+    }
+
+    // the end of the file when it was opened
+    fn end(&self) -> Option<ReadPosition> {
+        match self {
+            FileLines::Plain { file, position, .. } => {
+                file.try_clone().ok().map(|file| ReadPosition {
+                    file,
+                    offset: *position,
+                })
+            }
+            FileLines::Compressed(_) => None,
+        }
+    }
+
+    fn previous(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let (file, position, head, lines) = match self {
+            FileLines::Compressed(lines) => return Ok(lines.pop()),
+            FileLines::Plain {
+                file,
+                position,
+                head,
+                lines,
+            } => (file, position, head, lines),
+        };
+        while lines.is_empty() {
+            if *position == 0 {
+                return Ok((!head.is_empty()).then(|| std::mem::take(head)));
+            }
+            let size = READ_CHUNK_SIZE.min(*position as usize);
+            *position -= size as u64;
+            let mut content = vec![0; size];
+            file.seek(SeekFrom::Start(*position))?;
+            file.read_exact(&mut content)?;
+
+            content.append(head);
+            let mut chunk_lines = content.split(|b| *b == b'\n').map(|line| line.to_vec());
+            // the first line may begin in the previous chunk
+            *head = chunk_lines.next().unwrap_or_default();
+            lines.extend(chunk_lines);
+        }
+        Ok(lines.pop())
+    }
+}
+
+/// Selected lines of the log files of a service, from the oldest, read as they are
+/// iterated. The lines of both streams are merged in the order they were received, the
+/// files not written yet have no lines.
+pub fn stream_entries(
+    service: &Service,
+    filter: &LogFilter,
+) -> impl Iterator<Item = Result<LogEntry, Error>> {
+    stream_services(std::slice::from_ref(service), filter)
+        .map(|entry| entry.map(|(_, entry)| entry))
+}
+
+/// Selected lines of the log files of several services, from the oldest, along with
+/// the name of the service of each line. The files are read as the lines are iterated,
+/// and merged in the order the lines were received.
+pub fn stream_services(services: &[Service], filter: &LogFilter) -> StreamEntries {
+    let mut files = vec![];
+    for service in services {
+        for (stream, path) in filter.stream.files(service) {
+            // not written yet
+            let entries = Path::new(&path)
+                .exists()
+                .then(|| Entries::new(&path, stream, filter.clone()));
+            files.push(MergedFile {
+                service: service.name.clone(),
+                stream,
+                path,
+                entries,
+                next: None,
+            });
+        }
+    }
+    StreamEntries { files }
+}
+
+/// Line of a log file along with the name of its service.
+pub type ServiceEntry = (String, LogEntry);
+
+/// Selected lines of log files, merged as they are read.
+pub struct StreamEntries {
+    files: Vec<MergedFile>,
+}
+
+struct MergedFile {
+    service: String, // name of the service
+    stream: OutputStream,
+    path: String,
+    entries: Option<Entries>,
+    // read but not returned yet
+    next: Option<Option<Result<LogEntry, Error>>>,
+}
+
+impl MergedFile {
+    fn peek(&mut self) -> Option<&Result<LogEntry, Error>> {
+        let entries = self.entries.as_mut()?;
+        self.next.get_or_insert_with(|| entries.next()).as_ref()
+    }
+}
+
+impl StreamEntries {
+    /// Followers of the files from where they have been read, once all the lines are
+    /// read. The files not written yet are followed from their start.
+    pub fn followers(self) -> Vec<Followed> {
+        self.files
+            .into_iter()
+            .map(|file| Followed {
+                service: file.service,
+                stream: file.stream,
+                follower: LogFollower::from(
+                    &file.path,
+                    file.entries.and_then(Entries::into_position),
+                ),
+            })
+            .collect()
+    }
+}
+
+impl Iterator for StreamEntries {
+    type Item = Result<ServiceEntry, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // the file of the next line, the first one for the lines received at once
+        let mut next: Option<(usize, Option<DateTime<Utc>>)> = None;
+        for (index, file) in self.files.iter_mut().enumerate() {
+            let timestamp = match file.peek() {
+                Some(Ok(entry)) => entry.timestamp,
+                Some(Err(_)) => {
+                    let e = file.next.take()??.err()?;
+                    let message = format!("Cannot read {}: {}", file.path, e);
+                    return Some(Err(Error::msg(message)));
+                }
+                None => continue,
+            };
+            if next.is_none_or(|(_, next)| timestamp < next) {
+                next = Some((index, timestamp));
+            }
+        }
+        let file = &mut self.files[next?.0];
+        let entry = file.next.take()??;
+        Some(entry.map(|entry| (file.service.clone(), entry)))
+    }
+}
+
+/// A log file of a service, to follow from where its lines have been read.
+pub struct Followed {
+    pub service: String, // name of the service
+    pub stream: OutputStream,
+    pub follower: LogFollower,
+}
+
+/// Reads the selected lines of the log files of a service, only the `last` ones if
+/// given. The lines of both streams are merged in the order they were received, the
+/// files not written yet have no lines.
+pub fn read_stream(
+    service: &Service,
+    filter: &LogFilter,
+    last: Option<usize>,
+) -> Result<Vec<LogEntry>, Error> {
+    match last {
+        Some(last) => read_last_stream(service, filter, last).map(|(entries, _)| entries),
+        None => stream_entries(service, filter).collect(),
+    }
+}
+
+// the last selected lines of the log files of a service, along with the followers of
+// the files from their end
+fn read_last_stream(
+    service: &Service,
+    filter: &LogFilter,
+    last: usize,
+) -> Result<(Vec<LogEntry>, Vec<Followed>), Error> {
+    let files = filter.stream.files(service);
+    let mut entries = vec![];
+    let mut followed = vec![];
+    for (stream, path) in &files {
+        // not written yet, followed from its start
+        let mut position = None;
+        if Path::new(path).exists() {
+            let (file_entries, end) = read_last_entries(path, *stream, filter, last)
+                .map_err(|e| Error::msg(format!("Cannot read {}: {}", path, e)))?;
+            entries.extend(file_entries);
+            position = end;
+        }
+        followed.push(Followed {
+            service: service.name.clone(),
+            stream: *stream,
+            follower: LogFollower::from(path, position),
+        });
+    }
+    if files.len() > 1 {
+        // stable, the lines without a timestamp come first in the order of their files
+        entries.sort_by_key(|entry| entry.timestamp);
+    }
+    entries = entries.split_off(entries.len().saturating_sub(last));
+    Ok((entries, followed))
+}
+
+/// Reads the `last` selected lines of several services, merged in the order they were
+/// received, along with the name of the service of each line, and the followers of
+/// their files from where they have been read.
+pub fn tail_services(
+    services: &[Service],
+    filter: &LogFilter,
+    last: usize,
+) -> Result<(Vec<ServiceEntry>, Vec<Followed>), Error> {
+    let mut entries = vec![];
+    let mut followed = vec![];
+    for service in services {
+        let (service_entries, service_followed) = read_last_stream(service, filter, last)?;
+        entries.extend(
+            service_entries
+                .into_iter()
+                .map(|entry| (service.name.clone(), entry)),
+        );
+        followed.extend(service_followed);
+    }
+    if services.len() > 1 {
+        entries.sort_by_key(|(_, entry)| entry.timestamp);
+    }
+    entries = entries.split_off(entries.len().saturating_sub(last));
+    Ok((entries, followed))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{write::GzEncoder, Compression};
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn write_file(dir: &TempDir, name: &str, content: &[u8]) -> String {
+        let path = dir.path().join(name).to_str().unwrap().to_string();
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn write_gzip(dir: &TempDir, name: &str, content: &[u8]) -> String {
+        let path = dir.path().join(name).to_str().unwrap().to_string();
+        let mut encoder = GzEncoder::new(File::create(&path).unwrap(), Compression::default());
+        encoder.write_all(content).unwrap();
+        encoder.finish().unwrap();
+        path
+    }
+
+    // a line of stdout in the log file, received `seconds` after a fixed time
+    fn record(line: &str, partial: bool, pid: u32, seconds: i64) -> String {
+        record_on(OutputStream::Stdout, line, partial, pid, seconds)
+    }
+
+    fn record_on(
+        stream: OutputStream,
+        line: &str,
+        partial: bool,
+        pid: u32,
+        seconds: i64,
+    ) -> String {
+        let entry = LogEntry {
+            timestamp: Some(Utc.timestamp_opt(1_700_000_000 + seconds, 0).unwrap()),
+            stream,
+            service: "api".to_string(),
+            pid: Some(pid),
+            line: line.to_string(),
+            bytes: None,
+            partial,
+        };
+        serde_json::to_string(&entry).unwrap() + "\n"
+    }
+
+    fn read(path: &str, last: Option<usize>) -> Vec<String> {
+        read_entries(path, OutputStream::Stdout, &LogFilter::default(), last)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.line)
+            .collect()
+    }
+
+    // the last lines of stdout and stderr written to the same file
+    fn read_both(path: &str, last: usize) -> Vec<String> {
+        let filter = LogFilter {
+            stream: OutputStream::Both,
+            ..Default::default()
+        };
+        read_entries(path, OutputStream::Stdout, &filter, Some(last))
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.line)
+            .collect()
+    }
+
+    #[test]
+    fn reads_a_line_spanning_chunks() {
+        let dir = TempDir::new().unwrap();
+        let long = "x".repeat(READ_CHUNK_SIZE + 100);
+        let path = write_file(
+            &dir,
+            "out.log",
+            format!("first\n{}\nlast\n", long).as_bytes(),
+        );
+
+        assert_eq!(read(&path, Some(2)), [long.clone(), "last".to_string()]);
+        assert_eq!(read(&path, Some(3)), ["first", &long, "last"]);
+        assert_eq!(read(&path, None), ["first", &long, "last"]);
+    }
+
+    #[test]
+    fn reads_a_file_without_trailing_newline() {
+        let dir = TempDir::new().unwrap();
+        let path = write_file(&dir, "out.log", b"one\ntwo");
+
+        assert_eq!(read(&path, Some(1)), ["two"]);
+        assert_eq!(read(&path, Some(5)), ["one", "two"]);
+        assert_eq!(read(&path, None), ["one", "two"]);
+    }
+
+    #[test]
+    fn reads_the_rotated_files_from_the_oldest() {
+        let dir = TempDir::new().unwrap();
+        write_gzip(&dir, "out.log.2.gz", b"1\n2\n");
+        write_file(&dir, "out.log.1", b"3\n");
+        let path = write_file(&dir, "out.log", b"4\n5\n");
+
+        assert_eq!(read(&path, None), ["1", "2", "3", "4", "5"]);
+        assert_eq!(read(&path, Some(4)), ["2", "3", "4", "5"]);
+        assert_eq!(read(&path, Some(2)), ["4", "5"]);
+    }
+
+    #[test]
+    fn reads_a_rotated_file_compressed_since_it_was_listed() {
+        let dir = TempDir::new().unwrap();
+        let rotated = write_file(&dir, "out.log.1", b"1\n2\n");
+        let path = write_file(&dir, "out.log", b"3\n");
+        let entries = Entries::new(&path, OutputStream::Stdout, LogFilter::default());
+        let mut lines = BackwardLines {
+            files: rotation::rotated_files(&path).into(),
+            file: None,
+        };
+
+        write_gzip(&dir, "out.log.1.gz", b"1\n2\n");
+        std::fs::remove_file(&rotated).unwrap();
+        let read: Vec<_> = entries.map(|entry| entry.unwrap().line).collect();
+        assert_eq!(read, ["1", "2", "3"]);
+        assert_eq!(lines.next().unwrap().unwrap(), b"2");
+    }
+
+    #[test]
+    fn joins_the_parts_of_a_split_line() {
+        let dir = TempDir::new().unwrap();
+        let content = [
+            record("before", false, 1, 0),
+            record("abc", true, 1, 1),
+            record("def", true, 1, 2),
+            record("ghi", false, 1, 3),
+            // the end of the line has been lost along with its process
+            record("lost", true, 1, 4),
+            record("after", false, 2, 5),
+        ]
+        .concat();
+        let path = write_file(&dir, "out.log", content.as_bytes());
+
+        let joined = ["before", "abcdefghi", "lost", "after"];
+        assert_eq!(read(&path, None), joined);
+        assert_eq!(read(&path, Some(4)), joined);
+        assert_eq!(read(&path, Some(3)), joined[1..]);
+    }
+
+    #[test]
+    fn joins_a_line_split_across_rotated_files() {
+        let dir = TempDir::new().unwrap();
+        write_gzip(&dir, "out.log.1.gz", record("abc", true, 1, 0).as_bytes());
+        let path = write_file(&dir, "out.log", record("def", false, 1, 1).as_bytes());
+
+        assert_eq!(read(&path, None), ["abcdef"]);
+        assert_eq!(read(&path, Some(1)), ["abcdef"]);
+    }
+
+    #[test]
+    fn reads_the_last_lines_of_both_outputs_in_one_file() {
+        let dir = TempDir::new().unwrap();
+        let content = [
+            record("Z", false, 1, 0),
+            record("A", false, 1, 1),
+            record_on(OutputStream::Stderr, "B", false, 1, 2),
+        ]
+        .concat();
+        let path = write_file(&dir, "out.log", content.as_bytes());
+
+        assert_eq!(read_both(&path, 1), ["B"]);
+        assert_eq!(read_both(&path, 2), ["A", "B"]);
+    }
+
+    #[test]
+    fn joins_a_split_line_interleaved_with_the_other_output() {
+        let dir = TempDir::new().unwrap();
+        let content = [
+            record("Z", false, 1, 0),
+            record_on(OutputStream::Stderr, "be", true, 1, 1),
+            record("A", false, 1, 2),
+            record_on(OutputStream::Stderr, "gin", false, 1, 3),
+        ]
+        .concat();
+        let path = write_file(&dir, "out.log", content.as_bytes());
+
+        assert_eq!(read_both(&path, 1), ["A"]);
+        assert_eq!(read_both(&path, 2), ["begin", "A"]);
+        assert_eq!(read_both(&path, 3), ["Z", "begin", "A"]);
+    }
+
+    #[test]
+    fn selects_the_lines_received_since_a_time() {
+        let dir = TempDir::new().unwrap();
+        let content = [record("old", false, 1, 0), record("new", false, 1, 60)].concat();
+        let path = write_file(&dir, "out.log", content.as_bytes());
+        let filter = LogFilter {
+            since: Some(Utc.timestamp_opt(1_700_000_030, 0).unwrap()),
+            ..Default::default()
+        };
+
+        let entries = read_entries(&path, OutputStream::Stdout, &filter, None).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].line, "new");
+    }
+
+    #[test]
+    fn parses_relative_times() {
+        let before = Utc::now();
+        let time = parse_time("30s").unwrap();
+        assert!(time >= before - Duration::seconds(30));
+        assert!(time <= Utc::now() - Duration::seconds(30));
+
+        let time = parse_time("2h").unwrap();
+        assert!(time <= Utc::now() - Duration::hours(2));
+        assert!(time >= before - Duration::hours(2));
+    }
+
+    #[test]
+    fn parses_local_times_of_today() {
+        let time = parse_time("14:02").unwrap().with_timezone(&Local);
+        assert_eq!(time.date_naive(), Local::now().date_naive());
+        assert_eq!(time.time(), NaiveTime::from_hms_opt(14, 2, 0).unwrap());
+    }
+
+    #[test]
+    fn parses_rfc3339_times() {
+        let time = parse_time("2023-03-05T14:02:00+01:00").unwrap();
+        assert_eq!(time, Utc.with_ymd_and_hms(2023, 3, 5, 13, 2, 0).unwrap());
+    }
+
+    #[test]
+    fn rejects_invalid_times() {
+        for value in ["", "soon", "10y", "25:00"] {
+            assert!(parse_time(value).is_err(), "{}", value);
+        }
+    }
+}
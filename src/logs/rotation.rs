@@ -0,0 +1,185 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, ErrorKind, Write},
+    path::Path,
+    thread::{self, JoinHandle},
+    time::SystemTime,
+};
+
+use flate2::{read::MultiGzDecoder, write::GzEncoder, Compression};
+
+use crate::types::configuration::LogRotation;
+
+/// Log file of a service, opened in append mode so that the output of the previous
+/// runs is kept, and rotated according to the log rotation of the service.
+pub struct LogFile {
+    path: String,
+    file: File,
+    rotation: Option<LogRotation>,
+    size: u64,
+    opened: SystemTime,
+    // compression of the last rotated file, done without holding the file
+    compressing: Option<JoinHandle<()>>,
+}
+
+impl LogFile {
+    pub fn open(path: &str, rotation: Option<LogRotation>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let metadata = file.metadata()?;
+        // special files such as /dev/null are not rotated
+        let rotation = rotation.filter(|_| metadata.is_file());
+        Ok(Self {
+            path: path.to_string(),
+            file,
+            rotation,
+            size: metadata.len(),
+            opened: metadata.created().unwrap_or_else(|_| SystemTime::now()),
+            compressing: None,
+        })
+    }
+
+    /// Appends a line, after having rotated the file if the line doesn't fit in it
+    /// or if the file is too old.
+    pub fn write_line(&mut self, line: &[u8]) -> io::Result<()> {
+        if self.should_rotate(line.len() as u64) {
+            self.rotate()?;
+        }
+        self.file.write_all(line)?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    fn should_rotate(&self, len: u64) -> bool {
+        let rotation = match &self.rotation {
+            Some(rotation) => rotation,
+            None => return false,
+        };
+        // an empty file is written to anyway, even with a line larger than max_size
+        if self.size == 0 {
+            return false;
+        }
+        let full = rotation
+            .max_size
+            .map(|max_size| self.size + len > max_size)
+            .unwrap_or(false);
+        let expired = rotation
+            .get_max_age()
+            .map(|max_age| self.opened.elapsed().unwrap_or_default() >= max_age)
+            .unwrap_or(false);
+        full || expired
+    }
+
+    // shifts the rotated files, dropping the oldest one, and renames the current
+    // file to `<file>.1` before opening a new one, which is compressed in the background
+    fn rotate(&mut self) -> io::Result<()> {
+        let rotation = self.rotation.clone().unwrap();
+        // the previous `<file>.1` must not be shifted while it is compressed
+        if let Some(compressing) = self.compressing.take() {
+            let _ = compressing.join();
+        }
+        let max_files = rotation.get_max_files();
+        for compressed in [false, true] {
+            remove_if_exists(&segment(&self.path, max_files, compressed))?;
+        }
+        for index in (1..max_files).rev() {
+            for compressed in [false, true] {
+                let from = segment(&self.path, index, compressed);
+                if Path::new(&from).exists() {
+                    fs::rename(&from, segment(&self.path, index + 1, compressed))?;
+                }
+            }
+        }
+        let rotated = segment(&self.path, 1, false);
+        fs::rename(&self.path, &rotated)?;
+
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.size = 0;
+        self.opened = SystemTime::now();
+
+        if rotation.get_compress() {
+            self.compressing = Some(thread::spawn(move || {
+                if let Err(e) = compress(&rotated) {
+                    println!("Cannot compress {}: {}", rotated, e);
+                }
+            }));
+        }
+        Ok(())
+    }
+}
+
+/// Rotated files of a log file, the most recent first.
+pub fn rotated_files(path: &str) -> Vec<String> {
+    let mut files = vec![];
+    for index in 1.. {
+        // a rotated file is removed once it has been compressed, until then the
+        // compressed one is incomplete
+        let plain = segment(path, index, false);
+        let compressed = segment(path, index, true);
+        if Path::new(&plain).exists() {
+            files.push(plain);
+        } else if Path::new(&compressed).exists() {
+            files.push(compressed);
+        } else {
+            break;
+        }
+    }
+    files
+}
+
+/// Opens a log file for reading, or the file it has been compressed to if it is a
+/// rotated file compressed since it was listed, along with the path of the file opened.
+pub fn open(path: &str) -> io::Result<(String, File)> {
+    match File::open(path) {
+        Err(e) if e.kind() == ErrorKind::NotFound && !path.ends_with(".gz") => {
+            let compressed = format!("{}.gz", path);
+            match File::open(&compressed) {
+                Ok(file) => Ok((compressed, file)),
+                Err(_) => Err(e),
+            }
+        }
+        opened => opened.map(|file| (path.to_string(), file)),
+    }
+}
+
+/// Reader of a log file already opened, decompressing it if it has been gzipped.
+pub fn reader(path: &str, file: File) -> Box<dyn BufRead> {
+    match path.ends_with(".gz") {
+        true => Box::new(BufReader::new(MultiGzDecoder::new(file))),
+        false => Box::new(BufReader::new(file)),
+    }
+}
+
+fn segment(path: &str, index: u32, compressed: bool) -> String {
+    match compressed {
+        true => format!("{}.{}.gz", path, index),
+        false => format!("{}.{}", path, index),
+    }
+}
+
+fn remove_if_exists(path: &str) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+// the rotated file is read instead of its compressed one until it is removed, once the
+// compressed one is complete
+fn compress(path: &str) -> io::Result<()> {
+    let compressed = format!("{}.gz", path);
+    let written = File::open(path).and_then(|mut input| {
+        let mut encoder = GzEncoder::new(File::create(&compressed)?, Compression::default());
+        io::copy(&mut input, &mut encoder)?;
+        encoder.finish()
+    });
+    match written {
+        Ok(_) => fs::remove_file(path),
+        Err(e) => {
+            let _ = remove_if_exists(&compressed);
+            Err(e)
+        }
+    }
+}
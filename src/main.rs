@@ -1,15 +1,50 @@
 use anyhow::Error;
-use clap::{arg, Command};
+use clap::{arg, ArgMatches, Command};
 use superviseur::{
     cmd::{
-        config::execute_config, init::execute_init, list::execute_list, log::execute_log,
-        new::execute_new, ps::execute_ps, restart::execute_restart, start::execute_start,
-        status::execute_status, stop::execute_stop, tail::execute_tail, ui::execute_ui,
+        config::execute_config,
+        init::execute_init,
+        list::execute_list,
+        log::{execute_log, LogOptions},
+        new::execute_new,
+        ps::execute_ps,
+        restart::execute_restart,
+        start::execute_start,
+        status::execute_status,
+        stop::execute_stop,
+        tail::execute_tail,
+        ui::execute_ui,
+        up::execute_up,
     },
     server,
     types::configuration::ConfigFormat,
 };
 
+// format of the Superfile created by `init` or `new`, hcl by default
+fn config_format(args: &ArgMatches) -> ConfigFormat {
+    if args.is_present("toml") {
+        ConfigFormat::TOML
+    } else if args.is_present("json") {
+        ConfigFormat::JSON
+    } else if args.is_present("yaml") {
+        ConfigFormat::YAML
+    } else {
+        ConfigFormat::HCL
+    }
+}
+
+fn log_options(args: &ArgMatches) -> LogOptions<'_> {
+    LogOptions {
+        stream: args.value_of("stream").unwrap(),
+        since: args.value_of("since"),
+        until: args.value_of("until"),
+        grep: args.value_of("grep"),
+        invert_match: args.is_present("invert-match"),
+        level: args.value_of("level"),
+        timestamps: args.is_present("timestamps"),
+    }
+}
+
 fn cli() -> Command<'static> {
     const VESRION: &str = env!("CARGO_PKG_VERSION");
     Command::new("superviseur")
@@ -54,15 +89,43 @@ A simple process supervisor"#,
         .subcommand(Command::new("ps").about("List all running processes"))
         .subcommand(
             Command::new("log")
-                .arg(arg!(<name> "The name of the process to get the log of"))
-                .arg(arg!(--follow -f "Follow the log"))
+                .arg(arg!([name] ... "The names of the services to get the log of, all of them when none is given"))
+                .arg(arg!(-f --follow "Follow the log"))
+                .arg(
+                    arg!(--stream [stream] "The output to show: stdout, stderr or both")
+                        .possible_values(["stdout", "stderr", "both"])
+                        .default_value("stdout"),
+                )
+                .arg(arg!(--since [since] "Only the lines received since this time, e.g. 2023-03-05T14:02:00Z, 14:02 or 10m for 10 minutes ago"))
+                .arg(arg!(--until [until] "Only the lines received until this time"))
+                .arg(arg!(--timestamps "Show the time each line was received"))
+                .arg(arg!(--grep [pattern] "Only the lines matching this regular expression"))
+                .arg(arg!(-v --"invert-match" "Only the lines not matching the --grep pattern"))
+                .arg(
+                    arg!(--level [level] "Only the lines of this level or a more severe one")
+                        .possible_values(["trace", "debug", "info", "warn", "error"]),
+                )
                 .about("Get the log of a process"),
         )
         .subcommand(
             Command::new("tail")
-                .arg(arg!(<name> "The name of the process to tail the log of"))
-                .arg(arg!(--follow -f "Follow the log"))
-                .arg(arg!(--lines -n [lines] "The number of lines to tail"))
+                .arg(arg!([name] ... "The names of the services to tail the log of, all of them when none is given"))
+                .arg(arg!(-f --follow "Follow the log"))
+                .arg(arg!(-n --lines [lines] "The number of lines to tail"))
+                .arg(
+                    arg!(--stream [stream] "The output to show: stdout, stderr or both")
+                        .possible_values(["stdout", "stderr", "both"])
+                        .default_value("stdout"),
+                )
+                .arg(arg!(--since [since] "Only the lines received since this time, e.g. 2023-03-05T14:02:00Z, 14:02 or 10m for 10 minutes ago"))
+                .arg(arg!(--until [until] "Only the lines received until this time"))
+                .arg(arg!(--timestamps "Show the time each line was received"))
+                .arg(arg!(--grep [pattern] "Only the lines matching this regular expression"))
+                .arg(arg!(-v --"invert-match" "Only the lines not matching the --grep pattern"))
+                .arg(
+                    arg!(--level [level] "Only the lines of this level or a more severe one")
+                        .possible_values(["trace", "debug", "info", "warn", "error"]),
+                )
                 .about("Tail the log of a process"),
         )
         .subcommand(
@@ -73,20 +136,34 @@ A simple process supervisor"#,
         .subcommand(
             Command::new("init")
                 .arg(arg!(--toml "Initialize the superviseur config in toml format"))
+                .arg(arg!(--json "Initialize the superviseur config in json format"))
+                .arg(arg!(--yaml "Initialize the superviseur config in yaml format"))
                 .about("Initialize the superviseur config"),
         )
         .subcommand(
             Command::new("new")
                 .arg(arg!(--toml "Create a new service config in toml format"))
+                .arg(arg!(--json "Create a new service config in json format"))
+                .arg(arg!(--yaml "Create a new service config in yaml format"))
                 .about("Create a new service config"),
         )
         .subcommand(
             Command::new("serve")
                 .arg(arg!([port] "The port to listen on").default_value("5476"))
+                .arg(arg!(--"keep-children" "Leave the services running on exit, to be adopted by the next daemon"))
                 .about("Start the superviseur server"),
         )
-        .subcommand(Command::new("daemon").about("Start the superviseur daemon"))
-        .subcommand(Command::new("up").about("Start all services"))
+        .subcommand(
+            Command::new("daemon")
+                .arg(arg!(--"keep-children" "Leave the services running on exit, to be adopted by the next daemon"))
+                .about("Start the superviseur daemon"),
+        )
+        .subcommand(
+            Command::new("up")
+                .arg(arg!(--foreground "Run the services in the foreground, until they have all stopped"))
+                .arg(arg!(-a --attach "Print the output of the services, Ctrl-C stops them"))
+                .about("Start all services"),
+        )
         .subcommand(Command::new("down").about("Stop all services"))
         .subcommand(Command::new("ui").about("Start the superviseur ui"))
 }
@@ -115,36 +192,43 @@ async fn main() -> Result<(), Error> {
         Some(("list", _)) => execute_list().await?,
         Some(("ps", _)) => execute_ps().await?,
         Some(("log", args)) => {
-            let name = args.value_of("name");
+            let names: Vec<&str> = args
+                .values_of("name")
+                .map(|v| v.collect())
+                .unwrap_or_default();
             let follow = args.is_present("follow");
-            execute_log(name.unwrap(), follow).await?;
+            execute_log(&names, follow, log_options(args)).await?;
         }
         Some(("tail", args)) => {
-            let name = args.value_of("name");
+            let names: Vec<&str> = args
+                .values_of("name")
+                .map(|v| v.collect())
+                .unwrap_or_default();
             let follow = args.is_present("follow");
             let lines = args.value_of("lines");
             let lines = lines.map(|l| l.parse::<usize>().unwrap()).unwrap_or(10);
-            execute_tail(name.unwrap(), follow, lines).await?;
+            execute_tail(&names, follow, lines, log_options(args)).await?;
         }
         Some(("config", args)) => {
             let name = args.value_of("name");
             execute_config(name.unwrap());
         }
-        Some(("init", args)) => match args.is_present("toml") {
-            true => execute_init(ConfigFormat::TOML),
-            false => execute_init(ConfigFormat::HCL),
-        },
-        Some(("new", args)) => match args.is_present("toml") {
-            true => execute_new(ConfigFormat::TOML),
-            false => execute_new(ConfigFormat::HCL),
-        },
+        Some(("init", args)) => execute_init(config_format(args))?,
+        Some(("new", args)) => execute_new(config_format(args)),
         Some(("serve", args)) => {
             let port = args.value_of("port").unwrap();
             let port = port.parse::<u16>().unwrap();
-            server::exec(port, true).await?;
+            let keep_children = args.is_present("keep-children");
+            server::exec(port, true, keep_children, false).await?;
         }
-        Some(("daemon", _)) => server::exec(5476, false).await?,
-        Some(("up", _)) => execute_start(None).await?,
+        Some(("daemon", args)) => {
+            let keep_children = args.is_present("keep-children");
+            server::exec(5476, false, keep_children, false).await?;
+        }
+        Some(("up", args)) => match args.is_present("foreground") {
+            true => server::exec(5476, false, false, true).await?,
+            false => execute_up(args.is_present("attach")).await?,
+        },
         Some(("down", _)) => execute_stop(None).await?,
         Some(("ui", _)) => execute_ui().await?,
         _ => cli().print_help()?,
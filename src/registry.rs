@@ -0,0 +1,127 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::types::{configuration::ConfigurationData, process::Process};
+
+/// Configuration of a loaded project and the processes of its services.
+pub struct Project {
+    // configuration file the project was loaded from
+    pub path: String,
+    pub config: ConfigurationData,
+    // processes keyed by service id
+    processes: HashMap<String, Process>,
+    // service id of each service name
+    ids: HashMap<String, String>,
+}
+
+impl Project {
+    pub fn process(&self, id: &str) -> Option<&Process> {
+        self.processes.get(id)
+    }
+
+    pub fn process_by_name(&self, name: &str) -> Option<&Process> {
+        self.ids.get(name).and_then(|id| self.processes.get(id))
+    }
+
+    /// Processes in the order of the services in the configuration.
+    pub fn processes(&self) -> impl Iterator<Item = &Process> {
+        self.config
+            .services
+            .iter()
+            .filter_map(|s| s.id.as_ref().and_then(|id| self.processes.get(id)))
+    }
+}
+
+/// Projects loaded in the daemon, keyed by project name, along with the
+/// configuration file each one was loaded from. Shared by the engine, the gRPC
+/// services and the GraphQL schema.
+#[derive(Default)]
+pub struct Registry {
+    projects: BTreeMap<String, Project>,
+    // project of each loaded configuration file
+    paths: HashMap<String, String>,
+}
+
+impl Registry {
+    /// Registers the configuration loaded from `path`, keeping the processes of
+    /// the project if it is already loaded.
+    pub fn insert_config(&mut self, path: &str, config: ConfigurationData) {
+        let ids = config
+            .services
+            .iter()
+            .filter_map(|s| s.id.clone().map(|id| (s.name.clone(), id)))
+            .collect();
+        self.paths.insert(path.to_string(), config.project.clone());
+        match self.projects.get_mut(&config.project) {
+            Some(project) => {
+                project.path = path.to_string();
+                project.config = config;
+                project.ids = ids;
+            }
+            None => {
+                self.projects.insert(
+                    config.project.clone(),
+                    Project {
+                        path: path.to_string(),
+                        config,
+                        processes: HashMap::new(),
+                        ids,
+                    },
+                );
+            }
+        }
+    }
+
+    pub fn projects(&self) -> impl Iterator<Item = &Project> {
+        self.projects.values()
+    }
+
+    pub fn project(&self, project: &str) -> Option<&Project> {
+        self.projects.get(project)
+    }
+
+    pub fn project_by_path(&self, path: &str) -> Option<&Project> {
+        self.paths.get(path).and_then(|p| self.projects.get(p))
+    }
+
+    pub fn config(&self, project: &str) -> Option<&ConfigurationData> {
+        self.project(project).map(|p| &p.config)
+    }
+
+    pub fn config_by_path(&self, path: &str) -> Option<&ConfigurationData> {
+        self.project_by_path(path).map(|p| &p.config)
+    }
+
+    pub fn config_by_path_mut(&mut self, path: &str) -> Option<&mut ConfigurationData> {
+        let project = self.paths.get(path)?;
+        self.projects.get_mut(project).map(|p| &mut p.config)
+    }
+
+    pub fn process(&self, project: &str, id: &str) -> Option<&Process> {
+        self.project(project).and_then(|p| p.process(id))
+    }
+
+    pub fn process_mut(&mut self, project: &str, id: &str) -> Option<&mut Process> {
+        self.projects
+            .get_mut(project)
+            .and_then(|p| p.processes.get_mut(id))
+    }
+
+    /// Process of the service with the given id, whatever its project.
+    pub fn find_process(&self, id: &str) -> Option<&Process> {
+        self.projects.values().find_map(|p| p.process(id))
+    }
+
+    /// Adds the process of a service of a loaded project, keyed by its service id.
+    pub fn insert_process(&mut self, project: &str, process: Process) {
+        if let Some(project) = self.projects.get_mut(project) {
+            project
+                .processes
+                .insert(process.service_id.clone(), process);
+        }
+    }
+
+    /// Processes of all the projects.
+    pub fn processes(&self) -> impl Iterator<Item = &Process> {
+        self.projects.values().flat_map(|p| p.processes())
+    }
+}
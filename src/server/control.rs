@@ -1,24 +1,23 @@
-use std::{
-    collections::HashMap,
-    sync::{Arc, Mutex},
-    thread,
-    time::Duration,
-};
+use std::sync::{Arc, Mutex};
 
+use anyhow::Error;
 use names::Generator;
+use nix::unistd::{getpgid, Pid};
+use sysinfo::{PidExt, ProcessExt, System, SystemExt};
 use tokio::sync::mpsc;
 use tonic::{Request, Response};
 
 use crate::{
     api::{
-        objects::v1alpha1::Service,
+        objects::v1alpha1::{self, Service},
         superviseur::v1alpha1::{
             control_service_server::ControlService, ListRequest, ListResponse,
             ListRunningProcessesRequest, ListRunningProcessesResponse, LoadConfigRequest,
-            LoadConfigResponse, RestartRequest, RestartResponse, StartRequest, StartResponse,
-            StatusRequest, StatusResponse, StopRequest, StopResponse,
+            LoadConfigResponse, RestartRequest, RestartResponse, ServiceOutcome, StartRequest,
+            StartResponse, StatusRequest, StatusResponse, StopRequest, StopResponse,
         },
     },
+    graph::DependencyGraph,
     graphql::{
         self,
         schema::objects::subscriptions::{
@@ -26,10 +25,11 @@ use crate::{
         },
         simple_broker::SimpleBroker,
     },
-    superviseur::{ProcessEvent, Superviseur, SuperviseurCommand},
+    registry::Registry,
+    superviseur::{send_and_wait, CommandOutcome, ProcessEvent, SuperviseurCommand},
     types::{
         self,
-        configuration::ConfigurationData,
+        configuration::{ConfigFormat, ConfigurationData},
         process::{Process, State},
     },
 };
@@ -37,139 +37,109 @@ use crate::{
 pub struct Control {
     cmd_tx: mpsc::UnboundedSender<SuperviseurCommand>,
     event_tx: mpsc::UnboundedSender<ProcessEvent>,
-    superviseur: Superviseur,
-    processes: Arc<Mutex<Vec<(Process, String)>>>,
-    config_map: Arc<Mutex<HashMap<String, ConfigurationData>>>,
+    registry: Arc<Mutex<Registry>>,
 }
 
 impl Control {
     pub fn new(
         cmd_tx: mpsc::UnboundedSender<SuperviseurCommand>,
         event_tx: mpsc::UnboundedSender<ProcessEvent>,
-        superviseur: Superviseur,
-        processes: Arc<Mutex<Vec<(Process, String)>>>,
-        config_map: Arc<Mutex<HashMap<String, ConfigurationData>>>,
+        registry: Arc<Mutex<Registry>>,
     ) -> Self {
         Self {
             cmd_tx,
             event_tx,
-            superviseur,
-            processes,
-            config_map,
+            registry,
         }
     }
-}
 
-#[tonic::async_trait]
-impl ControlService for Control {
-    async fn load_config(
+    // validates the dependencies of the config, assigns the ids of its services and
+    // starts watching them
+    fn register_config(
         &self,
-        request: Request<LoadConfigRequest>,
-    ) -> Result<Response<LoadConfigResponse>, tonic::Status> {
-        let request = request.into_inner();
-        let config = request.config;
-        let path = request.file_path;
-        let mut config: ConfigurationData =
-            hcl::from_str(&config).map_err(|e| tonic::Status::internal(e.to_string()))?;
-
+        mut config: ConfigurationData,
+        path: &str,
+    ) -> Result<ConfigurationData, Error> {
         let mut generator = Generator::default();
-        let mut config_map = self.config_map.lock().unwrap();
-
-        // check if the config is already loaded
-        if config_map.contains_key(&path) {
-            // reuse the id of the services
-            let old_config = config_map.get_mut(&path).unwrap();
-            for service in &mut config.services {
-                match old_config.services.iter().find(|s| s.name == service.name) {
-                    Some(old_service) => {
-                        service.id = old_service.id.clone();
-
-                        // rewacth the directory if working_dir changed
-                        if old_service.working_dir != service.working_dir {
-                            self.cmd_tx
-                                .send(SuperviseurCommand::WatchForChanges(
-                                    service.working_dir.clone(),
-                                    service.clone(),
-                                    config.project.clone(),
-                                ))
-                                .unwrap();
-                        }
-                    }
-                    None => {
-                        service.id = Some(generator.next().unwrap());
+        let mut registry = self.registry.lock().unwrap();
+
+        // reuse the id of the services if the config is already loaded
+        let old_config = registry.config_by_path(path);
+        let mut watch = vec![];
+        for service in &mut config.services {
+            match old_config.and_then(|c| c.services.iter().find(|s| s.name == service.name)) {
+                Some(old_service) => {
+                    service.id = old_service.id.clone();
+
+                    // rewacth the directory if working_dir changed
+                    if old_service.working_dir != service.working_dir {
+                        watch.push(service.name.clone());
                     }
                 }
-            }
-            self.cmd_tx
-                .send(SuperviseurCommand::LoadConfig(config.clone(), path.clone()))
-                .unwrap();
-        } else {
-            config.services = config
-                .services
-                .into_iter()
-                .map(|mut service| {
+                None => {
                     service.id = Some(generator.next().unwrap());
-                    service
-                })
-                .collect();
-
-            config_map.insert(path.clone(), config.clone());
-
-            let services = config.services.clone();
-            let project = config.project.clone();
-
-            for service in services.into_iter() {
-                self.cmd_tx
-                    .send(SuperviseurCommand::WatchForChanges(
-                        service.working_dir.clone(),
-                        service,
-                        project.clone(),
-                    ))
-                    .unwrap();
+                    watch.push(service.name.clone());
+                }
             }
+        }
 
+        // convert services dependencies to ids
+        config.resolve_dependencies()?;
+
+        DependencyGraph::new(config.services.clone())?;
+
+        for service in config.services.iter().filter(|s| watch.contains(&s.name)) {
             self.cmd_tx
-                .send(SuperviseurCommand::LoadConfig(
-                    config.clone(),
+                .send(SuperviseurCommand::WatchForChanges(
+                    service.working_dir.clone(),
+                    service.clone(),
                     config.project.clone(),
                 ))
                 .unwrap();
         }
 
-        let config = config_map.get_mut(&path).unwrap();
+        registry.insert_config(path, config.clone());
+        Ok(config)
+    }
+}
 
-        let services = config.services.clone();
-        let mut services = services.into_iter();
+#[tonic::async_trait]
+impl ControlService for Control {
+    async fn load_config(
+        &self,
+        request: Request<LoadConfigRequest>,
+    ) -> Result<Response<LoadConfigResponse>, tonic::Status> {
+        let request = request.into_inner();
+        let config = request.config;
+        let path = request.file_path;
+        let format: ConfigFormat = request
+            .format
+            .parse()
+            .map_err(|e: Error| tonic::Status::invalid_argument(e.to_string()))?;
+        let config = format
+            .parse(&config)
+            .map_err(|e| tonic::Status::invalid_argument(e.to_string()))?;
 
-        // convert services dependencies to ids
-        for service in &mut config.services {
-            let mut dependencies = vec![];
-            for dependency in &service.depends_on {
-                match services.find(|s| s.name == *dependency) {
-                    Some(service) => {
-                        dependencies.push(service.id.clone().unwrap());
-                    }
-                    None => {
-                        return Err(tonic::Status::not_found(format!(
-                            "Service {} not found",
-                            dependency
-                        )));
-                    }
-                }
-            }
-            service.dependencies = dependencies;
+        for service in &config.services {
+            service
+                .validate()
+                .map_err(|e| tonic::Status::invalid_argument(e.to_string()))?;
         }
 
-        let services = config.services.clone();
-
-        for service in services.into_iter() {
-            self.cmd_tx
-                .send(SuperviseurCommand::Load(service, config.project.clone()))
-                .map_err(|e| tonic::Status::internal(e.to_string()))?;
+        let config = self
+            .register_config(config, &path)
+            .map_err(|e| tonic::Status::invalid_argument(e.to_string()))?;
+
+        let project = config.project.clone();
+        let outcome = send_and_wait(&self.cmd_tx, |reply| {
+            SuperviseurCommand::LoadConfig(config, project, Some(reply))
+        })
+        .await
+        .map_err(|e| tonic::Status::internal(e.to_string()))?;
+        if let CommandOutcome::Failed(error) = outcome {
+            return Err(tonic::Status::internal(error));
         }
 
-        thread::sleep(Duration::from_millis(100));
-
         Ok(Response::new(LoadConfigResponse { success: true }))
     }
 
@@ -180,13 +150,13 @@ impl ControlService for Control {
         let request = request.into_inner();
         let path = request.config_file_path;
         let name = request.name;
-        let config_map = self.config_map.lock().unwrap();
-
-        if !config_map.contains_key(&path) {
-            return Err(tonic::Status::not_found("Config file not found"));
-        }
-
-        let config = config_map.get(&path).unwrap();
+        let config = self
+            .registry
+            .lock()
+            .unwrap()
+            .config_by_path(&path)
+            .cloned()
+            .ok_or_else(|| tonic::Status::not_found("Config file not found"))?;
 
         if name.len() > 0 {
             let service = config
@@ -195,23 +165,24 @@ impl ControlService for Control {
                 .find(|s| s.name == name)
                 .ok_or_else(|| tonic::Status::not_found("Service not found"))?;
 
-            self.cmd_tx
-                .send(SuperviseurCommand::Start(
-                    service.clone(),
-                    config.project.clone(),
-                ))
-                .map_err(|e| tonic::Status::internal(e.to_string()))?;
-            return Ok(Response::new(StartResponse { success: true }));
+            let outcome = send_and_wait(&self.cmd_tx, |reply| {
+                SuperviseurCommand::Start(service.clone(), config.project.clone(), Some(reply))
+            })
+            .await
+            .map_err(|e| tonic::Status::internal(e.to_string()))?;
+            let (success, error, outcomes) = summarize(vec![(name, outcome)]);
+            return Ok(Response::new(StartResponse {
+                success,
+                error,
+                outcomes,
+            }));
         }
 
-        for service in &config.services {
-            self.cmd_tx
-                .send(SuperviseurCommand::Start(
-                    service.clone(),
-                    config.project.clone(),
-                ))
-                .map_err(|e| tonic::Status::internal(e.to_string()))?;
-        }
+        let outcomes = send_and_wait(&self.cmd_tx, |reply| {
+            SuperviseurCommand::StartAll(config.project.clone(), Some(reply))
+        })
+        .await
+        .map_err(|e| tonic::Status::internal(e.to_string()))?;
 
         let services = config.services.clone();
         let services = services
@@ -220,7 +191,12 @@ impl ControlService for Control {
             .collect::<Vec<graphql::schema::objects::service::Service>>();
         SimpleBroker::publish(AllServicesStarted { payload: services });
 
-        Ok(Response::new(StartResponse { success: true }))
+        let (success, error, outcomes) = summarize(outcomes);
+        Ok(Response::new(StartResponse {
+            success,
+            error,
+            outcomes,
+        }))
     }
 
     async fn stop(
@@ -230,13 +206,13 @@ impl ControlService for Control {
         let request = request.into_inner();
         let path = request.config_file_path;
         let name = request.name;
-        let config_map = self.config_map.lock().unwrap();
-
-        if !config_map.contains_key(&path) {
-            return Err(tonic::Status::not_found("Config file not found"));
-        }
-
-        let config = config_map.get(&path).unwrap();
+        let config = self
+            .registry
+            .lock()
+            .unwrap()
+            .config_by_path(&path)
+            .cloned()
+            .ok_or_else(|| tonic::Status::not_found("Config file not found"))?;
 
         if name.len() > 0 {
             let service = config
@@ -245,23 +221,24 @@ impl ControlService for Control {
                 .find(|s| s.name == name)
                 .ok_or_else(|| tonic::Status::not_found("Service not found"))?;
 
-            self.cmd_tx
-                .send(SuperviseurCommand::Stop(
-                    service.clone(),
-                    config.project.clone(),
-                ))
-                .unwrap();
-            return Ok(Response::new(StopResponse { success: true }));
+            let outcome = send_and_wait(&self.cmd_tx, |reply| {
+                SuperviseurCommand::Stop(service.clone(), config.project.clone(), Some(reply))
+            })
+            .await
+            .map_err(|e| tonic::Status::internal(e.to_string()))?;
+            let (success, error, outcomes) = summarize(vec![(name, outcome)]);
+            return Ok(Response::new(StopResponse {
+                success,
+                error,
+                outcomes,
+            }));
         }
 
-        for service in &config.services {
-            self.cmd_tx
-                .send(SuperviseurCommand::Stop(
-                    service.clone(),
-                    config.project.clone(),
-                ))
-                .unwrap();
-        }
+        let outcomes = send_and_wait(&self.cmd_tx, |reply| {
+            SuperviseurCommand::StopAll(config.project.clone(), Some(reply))
+        })
+        .await
+        .map_err(|e| tonic::Status::internal(e.to_string()))?;
 
         let services = config.services.clone();
         let services = services
@@ -270,7 +247,12 @@ impl ControlService for Control {
             .collect::<Vec<graphql::schema::objects::service::Service>>();
         SimpleBroker::publish(AllServicesStopped { payload: services });
 
-        Ok(Response::new(StopResponse { success: true }))
+        let (success, error, outcomes) = summarize(outcomes);
+        Ok(Response::new(StopResponse {
+            success,
+            error,
+            outcomes,
+        }))
     }
 
     async fn restart(
@@ -280,13 +262,13 @@ impl ControlService for Control {
         let request = request.into_inner();
         let path = request.config_file_path;
         let name = request.name;
-        let config_map = self.config_map.lock().unwrap();
-
-        if !config_map.contains_key(&path) {
-            return Err(tonic::Status::not_found("Config file not found"));
-        }
-
-        let config = config_map.get(&path).unwrap();
+        let config = self
+            .registry
+            .lock()
+            .unwrap()
+            .config_by_path(&path)
+            .cloned()
+            .ok_or_else(|| tonic::Status::not_found("Config file not found"))?;
 
         if name.len() > 0 {
             let service = config
@@ -295,23 +277,24 @@ impl ControlService for Control {
                 .find(|s| s.name == name)
                 .ok_or_else(|| tonic::Status::not_found("Service not found"))?;
 
-            self.cmd_tx
-                .send(SuperviseurCommand::Restart(
-                    service.clone(),
-                    config.project.clone(),
-                ))
-                .map_err(|e| tonic::Status::internal(e.to_string()))?;
-            return Ok(Response::new(RestartResponse { success: true }));
+            let outcome = send_and_wait(&self.cmd_tx, |reply| {
+                SuperviseurCommand::Restart(service.clone(), config.project.clone(), Some(reply))
+            })
+            .await
+            .map_err(|e| tonic::Status::internal(e.to_string()))?;
+            let (success, error, outcomes) = summarize(vec![(name, outcome)]);
+            return Ok(Response::new(RestartResponse {
+                success,
+                error,
+                outcomes,
+            }));
         }
 
-        for service in &config.services {
-            self.cmd_tx
-                .send(SuperviseurCommand::Restart(
-                    service.clone(),
-                    config.project.clone(),
-                ))
-                .map_err(|e| tonic::Status::internal(e.to_string()))?;
-        }
+        let outcomes = send_and_wait(&self.cmd_tx, |reply| {
+            SuperviseurCommand::RestartAll(config.project.clone(), Some(reply))
+        })
+        .await
+        .map_err(|e| tonic::Status::internal(e.to_string()))?;
 
         let services = config.services.clone();
         let services = services
@@ -320,7 +303,12 @@ impl ControlService for Control {
             .collect::<Vec<graphql::schema::objects::service::Service>>();
         SimpleBroker::publish(AllServicesRestarted { payload: services });
 
-        Ok(Response::new(RestartResponse { success: true }))
+        let (success, error, outcomes) = summarize(outcomes);
+        Ok(Response::new(RestartResponse {
+            success,
+            error,
+            outcomes,
+        }))
     }
 
     async fn status(
@@ -330,13 +318,11 @@ impl ControlService for Control {
         let request = request.into_inner();
         let path = request.config_file_path;
         let name = request.name;
-        let config_map = self.config_map.lock().unwrap();
-
-        if !config_map.contains_key(&path) {
-            return Err(tonic::Status::not_found("Config file not found"));
-        }
-
-        let config = config_map.get(&path).unwrap();
+        let registry = self.registry.lock().unwrap();
+        let project = registry
+            .project_by_path(&path)
+            .ok_or_else(|| tonic::Status::not_found("Config file not found"))?;
+        let config = &project.config;
 
         let service = config
             .services
@@ -344,27 +330,30 @@ impl ControlService for Control {
             .find(|s| s.name == name)
             .ok_or_else(|| tonic::Status::not_found("Service not found"))?;
 
-        let processes = self.processes.lock().unwrap();
-        let process = processes
-            .iter()
-            .find(|(p, _)| p.name == name && p.project == config.project)
-            .map(|(p, _)| p.clone())
-            .unwrap_or(Process {
-                name: name.clone(),
-                project: config.project.clone(),
-                r#type: service.r#type.clone(),
-                state: types::process::State::Stopped,
-                command: service.command.clone(),
-                description: service.description.clone(),
-                working_dir: service.working_dir.clone(),
-                env: service.env.clone(),
-                auto_restart: service.autorestart,
-                stdout: service.stdout.clone(),
-                stderr: service.stderr.clone(),
-                ..Default::default()
-            });
+        let process = project.process_by_name(&name).cloned().unwrap_or(Process {
+            name: name.clone(),
+            project: config.project.clone(),
+            r#type: service.r#type.clone(),
+            state: types::process::State::Stopped,
+            command: service.command.clone(),
+            description: service.description.clone(),
+            working_dir: service.working_dir.clone(),
+            env: service.env.clone(),
+            auto_restart: service.autorestart,
+            stdout: service.stdout.clone(),
+            stderr: service.stderr.clone(),
+            ..Default::default()
+        });
+
+        let children = match (&process.state, process.pid) {
+            (State::Running, Some(pid)) => process_tree(pid),
+            _ => vec![],
+        };
         Ok(Response::new(StatusResponse {
-            process: Some(process.into()),
+            process: Some(v1alpha1::Process {
+                children,
+                ..process.into()
+            }),
         }))
     }
 
@@ -374,25 +363,18 @@ impl ControlService for Control {
     ) -> Result<Response<ListResponse>, tonic::Status> {
         let request = request.into_inner();
         let path = request.config_file_path;
-        let config_map = self.config_map.lock().unwrap();
-
-        if !config_map.contains_key(&path) {
-            return Err(tonic::Status::not_found("Config file not found"));
-        }
+        let registry = self.registry.lock().unwrap();
+        let project = registry
+            .project_by_path(&path)
+            .ok_or_else(|| tonic::Status::not_found("Config file not found"))?;
 
-        let config = config_map.get(&path).unwrap();
-        let services = config.services.clone();
+        let services = project.config.services.clone();
         let mut list_response = ListResponse {
             services: services.into_iter().map(Service::from).collect(),
         };
 
-        let processes = self.processes.lock().unwrap();
         for service in list_response.services.iter_mut() {
-            let process = processes
-                .iter()
-                .find(|(p, _)| p.name == service.name)
-                .map(|(p, _)| p);
-            if let Some(process) = process {
+            if let Some(process) = project.process_by_name(&service.name) {
                 service.status = process.state.to_string().to_uppercase();
             } else {
                 service.status = "STOPPED".to_string();
@@ -406,14 +388,83 @@ impl ControlService for Control {
         &self,
         _request: Request<ListRunningProcessesRequest>,
     ) -> Result<Response<ListRunningProcessesResponse>, tonic::Status> {
-        let processes = self.processes.lock().unwrap();
+        let registry = self.registry.lock().unwrap();
         let list_response = ListRunningProcessesResponse {
-            processes: processes
-                .iter()
-                .filter(|(p, _)| p.state == State::Running)
-                .map(|(p, _)| Into::into(p.clone()))
+            processes: registry
+                .processes()
+                .filter(|p| p.state != State::Stopped)
+                .map(|p| Into::into(p.clone()))
                 .collect(),
         };
         Ok(Response::new(list_response))
     }
 }
+
+/// Lists the processes of the process group led by `pgid` (the process group of a
+/// service), depth first, the group leader itself excluded.
+fn process_tree(pgid: u32) -> Vec<v1alpha1::Process> {
+    let mut system = System::new();
+    system.refresh_processes();
+
+    let mut members: Vec<(u32, u32, String)> = system
+        .processes()
+        .values()
+        .filter(|p| p.pid().as_u32() != pgid)
+        .filter(|p| match getpgid(Some(Pid::from_raw(p.pid().as_u32() as i32))) {
+            Ok(group) => group.as_raw() as u32 == pgid,
+            Err(_) => false,
+        })
+        .map(|p| {
+            let command = match p.cmd().len() {
+                0 => p.name().to_string(),
+                _ => p.cmd().join(" "),
+            };
+            (
+                p.pid().as_u32(),
+                p.parent().map(|ppid| ppid.as_u32()).unwrap_or_default(),
+                command,
+            )
+        })
+        .collect();
+    members.sort();
+
+    // processes orphaned within the group are shown under the group leader
+    let pids: Vec<u32> = members.iter().map(|(pid, _, _)| *pid).collect();
+    for (_, ppid, _) in members.iter_mut() {
+        if !pids.contains(ppid) {
+            *ppid = pgid;
+        }
+    }
+
+    let mut tree = vec![];
+    push_children(pgid, &members, &mut tree);
+    tree
+}
+
+fn push_children(parent: u32, members: &[(u32, u32, String)], tree: &mut Vec<v1alpha1::Process>) {
+    for (pid, ppid, command) in members.iter().filter(|(_, ppid, _)| *ppid == parent) {
+        tree.push(v1alpha1::Process {
+            pid: *pid,
+            ppid: *ppid,
+            command: command.clone(),
+            ..Default::default()
+        });
+        push_children(*pid, members, tree);
+    }
+}
+
+// success of a command with the errors of the services that failed, one per line
+fn summarize(outcomes: Vec<(String, CommandOutcome)>) -> (bool, String, Vec<ServiceOutcome>) {
+    let errors = outcomes
+        .iter()
+        .filter_map(|(name, outcome)| match outcome {
+            CommandOutcome::Failed(error) => Some(format!("{}: {}", name, error)),
+            _ => None,
+        })
+        .collect::<Vec<String>>();
+    (
+        errors.is_empty(),
+        errors.join("\n"),
+        outcomes.into_iter().map(ServiceOutcome::from).collect(),
+    )
+}
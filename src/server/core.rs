@@ -1,5 +1,4 @@
 use std::{
-    collections::HashMap,
     error::Error,
     sync::{Arc, Mutex},
     thread,
@@ -13,33 +12,27 @@ use crate::{
         core_service_server::CoreService, GetVersionRequest, GetVersionResponse,
         StartWebDashboardRequest, StartWebDashboardResponse,
     },
-    superviseur::{ProcessEvent, Superviseur, SuperviseurCommand},
-    types::{configuration::ConfigurationData, process::Process},
+    registry::Registry,
+    superviseur::{ProcessEvent, SuperviseurCommand},
     webui::start_webui,
 };
 
 pub struct Core {
     cmd_tx: mpsc::UnboundedSender<SuperviseurCommand>,
     event_tx: mpsc::UnboundedSender<ProcessEvent>,
-    superviseur: Superviseur,
-    processes: Arc<Mutex<Vec<(Process, String)>>>,
-    config_map: Arc<Mutex<HashMap<String, ConfigurationData>>>,
+    registry: Arc<Mutex<Registry>>,
 }
 
 impl Core {
     pub fn new(
         cmd_tx: mpsc::UnboundedSender<SuperviseurCommand>,
         event_tx: mpsc::UnboundedSender<ProcessEvent>,
-        superviseur: Superviseur,
-        processes: Arc<Mutex<Vec<(Process, String)>>>,
-        config_map: Arc<Mutex<HashMap<String, ConfigurationData>>>,
+        registry: Arc<Mutex<Registry>>,
     ) -> Self {
         Self {
             cmd_tx,
             event_tx,
-            superviseur,
-            processes,
-            config_map,
+            registry,
         }
     }
 }
@@ -64,21 +57,12 @@ impl CoreService for Core {
 
         let cmd_tx = self.cmd_tx.clone();
         let event_tx = self.event_tx.clone();
-        let superviseur = self.superviseur.clone();
-        let processes = self.processes.clone();
-        let config_map = self.config_map.clone();
+        let registry = self.registry.clone();
 
         let rt = Handle::current();
 
-        thread::spawn(move || {
-            match rt.block_on(start_webui(
-                path,
-                cmd_tx,
-                event_tx,
-                superviseur,
-                processes,
-                config_map,
-            )) {
+        thread::spawn(
+            move || match rt.block_on(start_webui(path, cmd_tx, event_tx, registry)) {
                 Ok(_) => {
                     std::process::exit(0);
                 }
@@ -87,8 +71,8 @@ impl CoreService for Core {
                         std::process::exit(1);
                     }
                 }
-            }
-        });
+            },
+        );
 
         let ip = local_ip_addr::get_local_ip_address()
             .map_err(|e| tonic::Status::internal(e.to_string()))?;
@@ -1,37 +1,51 @@
-use std::{
-    collections::HashMap,
-    fs::File,
-    io::{BufRead, BufReader, Read},
-    sync::{Arc, Mutex},
-};
+use std::sync::{Arc, Mutex};
 
 use crate::{
     api::superviseur::v1alpha1::{
-        logging_service_server::LoggingService, LogRequest, LogResponse, TailRequest, TailResponse,
+        logging_service_server::LoggingService, AttachRequest, AttachResponse, LogRequest,
+        LogResponse, TailRequest, TailResponse,
+    },
+    logs::{
+        self,
+        follow::{follow, merge, LogFollower},
+        LogEntry, LogFilter, OutputStream,
     },
-    superviseur::Superviseur,
-    types::{configuration::ConfigurationData, process::Process},
+    registry::Registry,
+    types::configuration::Service,
 };
+use tokio::sync::mpsc;
 use tokio_stream::wrappers::ReceiverStream;
 use tonic::{Request, Response, Status};
 
 pub struct Logging {
-    superviseur: Superviseur,
-    processes: Arc<Mutex<Vec<(Process, String)>>>,
-    config_map: Arc<Mutex<HashMap<String, ConfigurationData>>>,
+    registry: Arc<Mutex<Registry>>,
 }
 
 impl Logging {
-    pub fn new(
-        superviseur: Superviseur,
-        processes: Arc<Mutex<Vec<(Process, String)>>>,
-        config_map: Arc<Mutex<HashMap<String, ConfigurationData>>>,
-    ) -> Self {
-        Self {
-            superviseur,
-            processes,
-            config_map,
+    pub fn new(registry: Arc<Mutex<Registry>>) -> Self {
+        Self { registry }
+    }
+
+    // the named services, or all of them when no name is given
+    fn find_services(&self, path: &str, names: &[String]) -> Result<Vec<Service>, anyhow::Error> {
+        let registry = self.registry.lock().unwrap();
+        let config = registry
+            .config_by_path(path)
+            .ok_or_else(|| anyhow::Error::msg("Config file not found"))?;
+        if names.is_empty() {
+            return Ok(config.services.clone());
         }
+        names
+            .iter()
+            .map(|name| {
+                config
+                    .services
+                    .iter()
+                    .find(|s| &s.name == name)
+                    .cloned()
+                    .ok_or_else(|| anyhow::Error::msg(format!("Service {} not found", name)))
+            })
+            .collect()
     }
 }
 
@@ -39,66 +53,52 @@ impl Logging {
 impl LoggingService for Logging {
     type TailStream = ReceiverStream<Result<TailResponse, Status>>;
     type LogStream = ReceiverStream<Result<LogResponse, Status>>;
+    type AttachStream = ReceiverStream<Result<AttachResponse, Status>>;
 
     async fn log(&self, request: Request<LogRequest>) -> Result<Response<Self::LogStream>, Status> {
         let request = request.into_inner();
-        let path = request.config_file_path;
-        let name = request.service;
-        let config_map = self.config_map.lock().unwrap();
-
-        if !config_map.contains_key(&path) {
-            return Err(tonic::Status::not_found("Config file not found"));
-        }
-
-        let config = config_map.get(&path).unwrap();
-
-        let service = config
-            .services
-            .iter()
-            .find(|s| s.name == name)
-            .ok_or_else(|| tonic::Status::not_found("Service not found"))?;
-
-        let log_file =
-            File::open(&service.stdout).map_err(|e| tonic::Status::internal(e.to_string()))?;
-
-        let (tx, rx) = tokio::sync::mpsc::channel(1024);
-
-        let reader = BufReader::new(log_file);
-
-        for line in reader.lines() {
-            let line = line.map_err(|e| tonic::Status::internal(e.to_string()))?;
-            let tx = tx.clone();
-            tokio::spawn(async move {
-                tx.send(Ok(LogResponse {
-                    line: line.to_string(),
-                }))
-                .await
-                .map_err(|e| tonic::Status::internal(e.to_string()))
-            });
-        }
-
-        if request.follow {
-            let tx = tx.clone();
-            let stdout = service.stdout.clone();
-            tokio::spawn(async move {
-                let mut file = File::open(&stdout).unwrap();
-                let mut buf = [0; 1024];
-                loop {
-                    let n = file.read(&mut buf).unwrap();
-                    if n == 0 {
-                        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-                        continue;
+        let names = requested_names(&request.service, &request.services);
+        let services = self
+            .find_services(&request.config_file_path, &names)
+            .map_err(|e| tonic::Status::not_found(e.to_string()))?;
+        let filter = LogFilter::parse(&request.stream, &request.since, &request.until)
+            .and_then(|filter| filter.search(&request.grep, request.invert_match, &request.level))
+            .map_err(|e| tonic::Status::invalid_argument(e.to_string()))?;
+
+        let (tx, rx) = mpsc::channel(1024);
+        tokio::spawn(async move {
+            // the files are read as the lines are sent, off the async workers, and followed
+            // from where they have been read
+            let history_tx = tx.clone();
+            let followers = tokio::task::spawn_blocking(move || {
+                let mut entries = logs::stream_services(&services, &filter);
+                for entry in entries.by_ref() {
+                    let response = entry
+                        .map(|(name, entry)| log_response(&name, entry))
+                        .map_err(|e| tonic::Status::internal(e.to_string()));
+                    if history_tx.blocking_send(response).is_err() {
+                        return None;
                     }
-                    let line = String::from_utf8_lossy(&buf[0..n]);
-                    let line = line.trim_end_matches('\n');
-                    tx.send(Ok(LogResponse {
-                        line: line.to_string(),
-                    }))
-                    .await
-                    .unwrap_or_default();
                 }
-            });
-        }
+                Some((entries.followers(), filter))
+            })
+            .await;
+            if let (true, Ok(Some((followers, filter)))) = (request.follow, followers) {
+                let tx = merge(tx);
+                for followed in followers {
+                    let name = followed.service;
+                    let response =
+                        move |entry: LogEntry| (entry.timestamp, log_response(&name, entry));
+                    tokio::spawn(follow(
+                        followed.follower,
+                        followed.stream,
+                        filter.clone(),
+                        tx.clone(),
+                        response,
+                    ));
+                }
+            }
+        });
 
         Ok(Response::new(ReceiverStream::new(rx)))
     }
@@ -108,72 +108,121 @@ impl LoggingService for Logging {
         request: Request<TailRequest>,
     ) -> Result<Response<Self::TailStream>, Status> {
         let request = request.into_inner();
-        let path = request.config_file_path;
-        let name = request.service;
-        let config_map = self.config_map.lock().unwrap();
+        let names = requested_names(&request.service, &request.services);
+        let services = self
+            .find_services(&request.config_file_path, &names)
+            .map_err(|e| tonic::Status::not_found(e.to_string()))?;
+        let filter = LogFilter::parse(&request.stream, &request.since, &request.until)
+            .and_then(|filter| filter.search(&request.grep, request.invert_match, &request.level))
+            .map_err(|e| tonic::Status::invalid_argument(e.to_string()))?;
+
+        let (entries, followers) = logs::tail_services(&services, &filter, request.lines as usize)
+            .map_err(|e| tonic::Status::internal(e.to_string()))?;
+
+        let (tx, rx) = mpsc::channel(1024);
+        tokio::spawn(async move {
+            for (name, entry) in entries {
+                if tx.send(Ok(tail_response(&name, entry))).await.is_err() {
+                    return;
+                }
+            }
+            // from where the files have been read, not to miss the lines written since
+            if request.follow {
+                let tx = merge(tx);
+                for followed in followers {
+                    let name = followed.service;
+                    let response =
+                        move |entry: LogEntry| (entry.timestamp, tail_response(&name, entry));
+                    tokio::spawn(follow(
+                        followed.follower,
+                        followed.stream,
+                        filter.clone(),
+                        tx.clone(),
+                        response,
+                    ));
+                }
+            }
+        });
 
-        if !config_map.contains_key(&path) {
-            return Err(tonic::Status::not_found("Config file not found"));
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn attach(
+        &self,
+        request: Request<AttachRequest>,
+    ) -> Result<Response<Self::AttachStream>, Status> {
+        let request = request.into_inner();
+        let path = request.config_file_path;
+        let config = self
+            .registry
+            .lock()
+            .unwrap()
+            .config_by_path(&path)
+            .cloned()
+            .ok_or_else(|| tonic::Status::not_found("Config file not found"))?;
+
+        let (tx, rx) = mpsc::channel(1024);
+        let filter = LogFilter {
+            stream: OutputStream::Both,
+            ..Default::default()
+        };
+        let tx = merge(tx);
+        for service in config.services {
+            for (stream, path) in OutputStream::Both.files(&service) {
+                let name = service.name.clone();
+                // opened before the stream is returned, not to miss the lines written
+                // once the client knows it is attached
+                let follower = LogFollower::new(&path);
+                tokio::spawn(follow(
+                    follower,
+                    stream,
+                    filter.clone(),
+                    tx.clone(),
+                    move |entry| {
+                        let response = AttachResponse {
+                            service: name.clone(),
+                            stream: entry.stream.to_string(),
+                            timestamp: entry.rfc3339(),
+                            raw: entry.invalid_bytes(),
+                            line: entry.line,
+                        };
+                        (entry.timestamp, response)
+                    },
+                ));
+            }
         }
 
-        let config = config_map.get(&path).unwrap();
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+}
 
-        let service = config
-            .services
-            .iter()
-            .find(|s| s.name == name)
-            .ok_or_else(|| tonic::Status::not_found("Service not found"))?;
-
-        let log_file =
-            File::open(&service.stdout).map_err(|e| tonic::Status::internal(e.to_string()))?;
-
-        let (tx, rx) = tokio::sync::mpsc::channel(1024);
-
-        let reader = BufReader::new(log_file);
-        let num_lines = request.lines as usize;
-
-        // Read the last `num_lines` lines of the file
-        let mut lines: Vec<String> = reader
-            .lines()
-            .map(|line| line.unwrap())
-            .collect::<Vec<String>>();
-
-        let tail_lines = lines.split_off(lines.len().saturating_sub(num_lines));
-
-        for line in tail_lines {
-            let tx = tx.clone();
-            tokio::spawn(async move {
-                tx.send(Ok(TailResponse {
-                    line: line.to_string(),
-                }))
-                .await
-                .map_err(|e| tonic::Status::internal(e.to_string()))
-            });
-        }
+// the service of the request followed by the other services
+fn requested_names(service: &str, services: &[String]) -> Vec<String> {
+    [service.to_string()]
+        .into_iter()
+        .filter(|name| !name.is_empty())
+        .chain(services.iter().cloned())
+        .collect()
+}
 
-        if request.follow {
-            let tx = tx.clone();
-            let stdout = service.stdout.clone();
-            tokio::spawn(async move {
-                let mut file = File::open(&stdout).unwrap();
-                let mut buf = [0; 1024];
-                loop {
-                    let n = file.read(&mut buf).unwrap();
-                    if n == 0 {
-                        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-                        continue;
-                    }
-                    let line = String::from_utf8_lossy(&buf[0..n]);
-                    let line = line.trim_end_matches('\n');
-                    tx.send(Ok(TailResponse {
-                        line: line.to_string(),
-                    }))
-                    .await
-                    .unwrap();
-                }
-            });
-        }
+fn log_response(service: &str, entry: LogEntry) -> LogResponse {
+    LogResponse {
+        service: service.to_string(),
+        stream: entry.stream.to_string(),
+        timestamp: entry.rfc3339(),
+        pid: entry.pid.unwrap_or_default(),
+        raw: entry.invalid_bytes(),
+        line: entry.line,
+    }
+}
 
-        Ok(Response::new(ReceiverStream::new(rx)))
+fn tail_response(service: &str, entry: LogEntry) -> TailResponse {
+    TailResponse {
+        service: service.to_string(),
+        stream: entry.stream.to_string(),
+        timestamp: entry.rfc3339(),
+        pid: entry.pid.unwrap_or_default(),
+        raw: entry.invalid_bytes(),
+        line: entry.line,
     }
 }
@@ -1,5 +1,4 @@
 use std::{
-    collections::HashMap,
     net::SocketAddr,
     path::PathBuf,
     sync::{Arc, Mutex},
@@ -7,25 +6,46 @@ use std::{
 
 use crate::{
     api::superviseur::v1alpha1::{
-        control_service_server::ControlServiceServer, core_service_server::CoreServiceServer,
+        control_service_server::{ControlService, ControlServiceServer},
+        core_service_server::CoreServiceServer,
         logging_service_server::LoggingServiceServer,
+        LoadConfigRequest, StartRequest,
     },
+    cmd::outcome::print_outcomes,
+    config::verify_if_config_file_is_present,
+    init,
+    registry::Registry,
     server::{control::Control, logging::Logging},
-    superviseur::Superviseur,
-    types::{process::Process, BANNER, UNIX_SOCKET_PATH},
+    superviseur::{self, SuperviseurCommand},
+    types::{BANNER, UNIX_SOCKET_PATH},
 };
 use anyhow::Error;
 use owo_colors::OwoColorize;
-use tokio::net::UnixListener;
+use tokio::{
+    net::UnixListener,
+    signal::unix::{signal, SignalKind},
+    sync::{mpsc::UnboundedSender, oneshot},
+};
 use tokio_stream::wrappers::UnixListenerStream;
-use tonic::transport::Server;
+use tonic::{transport::Server, Request};
 
 pub mod control;
 pub mod core;
 pub mod logging;
 
-pub async fn exec(port: u16, serve: bool) -> Result<(), Error> {
-    let addr: SocketAddr = format!("0.0.0.0:{}", port).parse().unwrap();
+/// Runs the daemon. In the foreground mode, it loads and starts the Superfile of the
+/// current directory itself, and it exits once all the services have stopped, as it
+/// does when it runs as the init process of a container.
+pub async fn exec(
+    port: u16,
+    serve: bool,
+    keep_children: bool,
+    foreground: bool,
+) -> Result<(), Error> {
+    let mut sigint = signal(SignalKind::interrupt())?;
+    let mut sigterm = signal(SignalKind::terminate())?;
+    let addr: SocketAddr = format!("0.0.0.0:{}", port).parse()?;
+    let listener = bind_socket()?;
     println!("{}", BANNER.bright_purple());
     println!(
         "Listening on {}{} 🚀",
@@ -36,94 +56,170 @@ pub async fn exec(port: u16, serve: bool) -> Result<(), Error> {
         println!("Listening on {} 🚀", addr.cyan());
     }
 
-    let config_map = Arc::new(Mutex::new(HashMap::new()));
+    let registry = Arc::new(Mutex::new(Registry::default()));
     let (cmd_tx, cmd_rx) = tokio::sync::mpsc::unbounded_channel();
     let (event_tx, events) = tokio::sync::mpsc::unbounded_channel();
-    let processes = Arc::new(Mutex::new(vec![] as Vec<(Process, String)>));
-    let cmd_rx = Arc::new(Mutex::new(cmd_rx));
 
-    let superviseur = Superviseur::new(
+    superviseur::spawn(
         cmd_rx,
         cmd_tx.clone(),
         event_tx.clone(),
         events,
-        processes.clone(),
-        config_map.clone(),
+        registry.clone(),
+        !foreground,
     );
 
+    let (exit_tx, mut exit_rx) = oneshot::channel();
+    let exit_when_stopped = foreground || init::is_pid1();
+    if exit_when_stopped {
+        init::handle_signals(cmd_tx.clone())?;
+        cmd_tx
+            .send(SuperviseurCommand::ExitWhenStopped(exit_tx))
+            .unwrap();
+    }
+    let control = Control::new(cmd_tx.clone(), event_tx.clone(), registry.clone());
+
     let cloned_cmd_tx = cmd_tx.clone();
     let cloned_event_tx = event_tx.clone();
-    let cloned_superviseur = superviseur.clone();
-    let cloned_processes = processes.clone();
-    let cloned_config_map = config_map.clone();
+    let cloned_registry = registry.clone();
+    let shutdown_tx = cmd_tx.clone();
 
-    // create a one-shot channel to wait for the server to start
-    let (tx, rx) = tokio::sync::oneshot::channel::<bool>();
+    // create a one-shot channel to wait for the server to stop
+    let (tx, rx) = tokio::sync::oneshot::channel();
 
     tokio::spawn(async move {
-        let socket_path = PathBuf::from(UNIX_SOCKET_PATH);
-
-        if socket_path.exists() {
-            std::fs::remove_file(&socket_path).unwrap();
-        }
-
-        let listener = UnixListener::bind(&socket_path).unwrap();
-
-        Server::builder()
+        let result = Server::builder()
             .accept_http1(true)
             .add_service(tonic_web::enable(LoggingServiceServer::new(Logging::new(
-                cloned_superviseur.clone(),
-                cloned_processes.clone(),
-                cloned_config_map.clone(),
+                cloned_registry.clone(),
             ))))
             .add_service(tonic_web::enable(ControlServiceServer::new(Control::new(
                 cloned_cmd_tx.clone(),
                 cloned_event_tx.clone(),
-                cloned_superviseur.clone(),
-                cloned_processes.clone(),
-                cloned_config_map.clone(),
+                cloned_registry.clone(),
             ))))
             .add_service(tonic_web::enable(CoreServiceServer::new(core::Core::new(
                 cloned_cmd_tx,
                 cloned_event_tx,
-                cloned_superviseur,
-                cloned_processes,
-                cloned_config_map,
+                cloned_registry,
             ))))
             .serve_with_incoming(UnixListenerStream::new(listener))
-            .await
-            .unwrap();
-        tx.send(true).unwrap();
+            .await;
+        let _ = tx.send(result);
     });
 
-    if serve {
+    let tcp_server = async {
+        if !serve {
+            return std::future::pending().await;
+        }
         Server::builder()
             .accept_http1(true)
             .add_service(tonic_web::enable(LoggingServiceServer::new(Logging::new(
-                superviseur.clone(),
-                processes.clone(),
-                config_map.clone(),
+                registry.clone(),
             ))))
             .add_service(tonic_web::enable(ControlServiceServer::new(Control::new(
                 cmd_tx.clone(),
                 event_tx.clone(),
-                superviseur.clone(),
-                processes.clone(),
-                config_map.clone(),
+                registry.clone(),
             ))))
             .add_service(tonic_web::enable(CoreServiceServer::new(core::Core::new(
-                cmd_tx,
-                event_tx,
-                superviseur,
-                processes,
-                config_map,
+                cmd_tx, event_tx, registry,
             ))))
             .serve(addr)
-            .await?;
+            .await
+    };
+
+    let up = up(&control);
+    tokio::pin!(tcp_server, up, rx);
+    let mut started = !foreground;
+    loop {
+        tokio::select! {
+            result = &mut up, if !started => {
+                started = true;
+                if let Err(e) = result {
+                    shutdown(&shutdown_tx, false).await?;
+                    return Err(e);
+                }
+            }
+            result = &mut tcp_server => return Ok(result?),
+            // wait for the server to stop
+            result = &mut rx => return Ok(result??),
+            Ok(code) = &mut exit_rx, if exit_when_stopped => {
+                let socket_path = PathBuf::from(UNIX_SOCKET_PATH);
+                if socket_path.exists() {
+                    std::fs::remove_file(&socket_path)?;
+                }
+                std::process::exit(code);
+            }
+            _ = sigint.recv() => return shutdown(&shutdown_tx, keep_children).await,
+            _ = sigterm.recv() => return shutdown(&shutdown_tx, keep_children).await,
+        }
+    }
+}
+
+// binds the socket of the daemon, replacing the one left by a daemon which didn't exit
+// cleanly, but not the one of a daemon still listening on it
+fn bind_socket() -> Result<UnixListener, Error> {
+    let socket_path = PathBuf::from(UNIX_SOCKET_PATH);
+    if socket_path.exists() {
+        if std::os::unix::net::UnixStream::connect(&socket_path).is_ok() {
+            return Err(Error::msg(format!(
+                "Another superviseur daemon is listening on unix:{}",
+                UNIX_SOCKET_PATH
+            )));
+        }
+        std::fs::remove_file(&socket_path)?;
+    }
+    Ok(UnixListener::bind(&socket_path)?)
+}
+
+// loads the Superfile of the current directory and starts its services, as `up`
+// does through the socket
+async fn up(control: &Control) -> Result<(), Error> {
+    let superfile = verify_if_config_file_is_present()?;
+    let current_dir = std::env::current_dir()?;
+    let config = superfile.read()?;
+    let path = current_dir.to_str().unwrap().to_string();
+
+    control
+        .load_config(Request::new(LoadConfigRequest {
+            config,
+            file_path: path.clone(),
+            format: superfile.format.to_string(),
+        }))
+        .await?;
+
+    let response = control
+        .start(Request::new(StartRequest {
+            name: String::new(),
+            config_file_path: path,
+        }))
+        .await?
+        .into_inner();
+    print_outcomes(&response.outcomes);
+    // the services which could be started keep running
+    if !response.success {
+        println!("{}", response.error);
     }
+    Ok(())
+}
 
-    // wait for the server to start
-    rx.await.unwrap();
+// stops the services, unless they are left running for the next daemon to adopt,
+// then removes the socket
+async fn shutdown(
+    cmd_tx: &UnboundedSender<SuperviseurCommand>,
+    keep_children: bool,
+) -> Result<(), Error> {
+    println!("Shutting down...");
+    let (reply_tx, reply_rx) = oneshot::channel();
+    cmd_tx
+        .send(SuperviseurCommand::Shutdown(keep_children, reply_tx))
+        .unwrap();
+    reply_rx.await?;
 
+    let socket_path = PathBuf::from(UNIX_SOCKET_PATH);
+    if socket_path.exists() {
+        std::fs::remove_file(&socket_path)?;
+    }
     Ok(())
 }
@@ -0,0 +1,136 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::Error;
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use sysinfo::{PidExt, ProcessExt, System, SystemExt};
+
+use crate::{
+    registry::Registry,
+    types::{configuration::ConfigurationData, process::State},
+};
+
+/// Whether a service should be running, as left by the last start or stop.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DesiredState {
+    Running,
+    Stopped,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ServiceState {
+    pub id: String,
+    pub name: String,
+    pub desired_state: DesiredState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pid: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<i64>, // unix timestamp of the start of the process
+    pub restarts: u32,
+    // stopped with `superviseur stop`, so not started again by autostart
+    #[serde(default)]
+    pub stopped_by_user: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProjectState {
+    pub path: String,
+    pub config: ConfigurationData,
+    pub services: Vec<ServiceState>,
+}
+
+/// Projects and services of the daemon, saved to `state.json` in the user's data
+/// dir so that a restarted daemon can load the projects again and adopt the
+/// services still running.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct DaemonState {
+    pub projects: Vec<ProjectState>,
+}
+
+impl From<&Registry> for DaemonState {
+    fn from(registry: &Registry) -> Self {
+        let projects = registry
+            .projects()
+            .map(|project| ProjectState {
+                path: project.path.clone(),
+                config: project.config.clone(),
+                services: project
+                    .processes()
+                    .map(|p| {
+                        let desired_state = match p.state {
+                            State::Running | State::Waiting | State::Backoff => {
+                                DesiredState::Running
+                            }
+                            _ => DesiredState::Stopped,
+                        };
+                        let running = p.state == State::Running;
+                        ServiceState {
+                            id: p.service_id.clone(),
+                            name: p.name.clone(),
+                            desired_state,
+                            pid: p.pid.filter(|_| running),
+                            started_at: p.up_time.filter(|_| running).map(|t| t.timestamp()),
+                            restarts: p.restarts,
+                            stopped_by_user: p.stopped_by_user,
+                        }
+                    })
+                    .collect(),
+            })
+            .collect();
+        Self { projects }
+    }
+}
+
+impl DaemonState {
+    pub fn path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("superviseur").join("state.json"))
+    }
+
+    /// Reads the state saved by the previous daemon, if any.
+    pub fn load() -> Result<Option<Self>, Error> {
+        let path = match Self::path() {
+            Some(path) if path.exists() => path,
+            _ => return Ok(None),
+        };
+        let content = fs::read_to_string(&path)?;
+        Ok(Some(serde_json::from_str(&content)?))
+    }
+
+    /// Writes the serialized state, through a temporary file so that a crash
+    /// never leaves a truncated state behind.
+    pub fn save(content: &str) -> Result<(), Error> {
+        let path = Self::path().ok_or_else(|| Error::msg("No data directory found"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, content)?;
+        fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+}
+
+impl ServiceState {
+    pub fn started_at(&self) -> Option<DateTime<Utc>> {
+        self.started_at
+            .and_then(|t| Utc.timestamp_opt(t, 0).single())
+    }
+}
+
+/// Checks that `pid` is still the process spawned for `command` at `started_at`,
+/// and not another process which reused its pid.
+pub fn is_same_process(pid: u32, command: &str, started_at: DateTime<Utc>) -> bool {
+    let mut system = System::new();
+    let pid = sysinfo::Pid::from_u32(pid);
+    if !system.refresh_process(pid) {
+        return false;
+    }
+    let process = match system.process(pid) {
+        Some(process) => process,
+        None => return false,
+    };
+    // the start time is only known to the second
+    let started = (process.start_time() as i64 - started_at.timestamp()).abs() <= 2;
+    started && process.cmd() == ["sh", "-c", command]
+}
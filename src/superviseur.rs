@@ -1,68 +1,96 @@
 use std::{
-    collections::HashMap,
-    io::{BufRead, Write},
-    pin::Pin,
+    collections::{HashMap, HashSet},
+    os::unix::process::{CommandExt, ExitStatusExt},
+    process::ExitStatus,
     sync::{Arc, Mutex},
-    task::{Context, Poll},
-    thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use anyhow::Error;
-use futures::Future;
+use chrono::{DateTime, Utc};
 use nix::{
     sys::signal::{self, Signal},
     unistd::Pid,
 };
-use tokio::sync::mpsc;
+use tokio::{
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, BufReader},
+    net::unix::pipe::Receiver,
+    process::Child,
+    sync::{mpsc, oneshot},
+    task::JoinSet,
+};
 
 use crate::{
+    graph::DependencyGraph,
     graphql::{
         schema::{
             self,
             objects::subscriptions::{
                 AllServicesRestarted, AllServicesStarted, AllServicesStopped, LogStream,
-                ServiceRestarted, ServiceStarted, ServiceStopped, TailLogStream,
+                ServiceHealthChanged, ServiceRestarted, ServiceStarted, ServiceStopped,
+                TailLogStream,
             },
         },
         simple_broker::SimpleBroker,
     },
+    health::HealthChecker,
+    init,
+    logs::{fifo, rotation::LogFile, LogEntry, OutputStream},
+    registry::{Project, Registry},
+    state::{self, DaemonState, DesiredState},
     types::{
-        configuration::{ConfigurationData, Service},
-        process::{Process, State},
+        configuration::{
+            ConfigurationData, Dependency, DependencyCondition, RestartPolicy, Service,
+        },
+        process::{Health, Process, State},
     },
     watch::WatchForChanges,
 };
 
-#[derive(Clone)]
-pub struct Superviseur {}
+/// Spawns the engine as a task of the current tokio runtime.
+pub fn spawn(
+    cmd_rx: mpsc::UnboundedReceiver<SuperviseurCommand>,
+    cmd_tx: mpsc::UnboundedSender<SuperviseurCommand>,
+    event_tx: mpsc::UnboundedSender<ProcessEvent>,
+    events: mpsc::UnboundedReceiver<ProcessEvent>,
+    registry: Arc<Mutex<Registry>>,
+    persist: bool,
+) {
+    let internal = SuperviseurInternal::new(cmd_rx, cmd_tx, event_tx, events, registry, persist);
+    tokio::spawn(internal.run());
+}
 
-impl Superviseur {
-    pub fn new(
-        cmd_rx: Arc<Mutex<mpsc::UnboundedReceiver<SuperviseurCommand>>>,
-        cmd_tx: mpsc::UnboundedSender<SuperviseurCommand>,
-        event_tx: mpsc::UnboundedSender<ProcessEvent>,
-        events: mpsc::UnboundedReceiver<ProcessEvent>,
-        processes: Arc<Mutex<Vec<(Process, String)>>>,
-        config_map: Arc<Mutex<HashMap<String, ConfigurationData>>>,
-    ) -> Self {
-        thread::spawn(move || {
-            let internal =
-                SuperviseurInternal::new(cmd_rx, cmd_tx, event_tx, events, processes, config_map);
-            futures::executor::block_on(internal);
-        });
-        Self {}
-    }
+/// Outcome of a command, sent back on its reply channel once the engine has handled it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandOutcome {
+    Started(u32),
+    AlreadyRunning(u32),
+    Stopped,
+    NotRunning,
+    Loaded,
+    Failed(String),
 }
 
+pub type Reply = oneshot::Sender<CommandOutcome>;
+
+/// Reply of the commands applying to all the services of a project, with the outcome
+/// of each service.
+pub type ReplyAll = oneshot::Sender<Vec<(String, CommandOutcome)>>;
+
 #[derive(Debug)]
 pub enum SuperviseurCommand {
-    Load(Service, String),
-    Start(Service, String),
-    Stop(Service, String),
-    Restart(Service, String),
-    LoadConfig(ConfigurationData, String),
+    Start(Service, String, Option<Reply>),
+    StartAll(String, Option<ReplyAll>),
+    Stop(Service, String, Option<Reply>),
+    StopAll(String, Option<ReplyAll>),
+    Restart(Service, String, Option<Reply>),
+    RestartAll(String, Option<ReplyAll>),
+    LoadConfig(ConfigurationData, String, Option<Reply>),
     WatchForChanges(String, Service, String),
+    Shutdown(bool, oneshot::Sender<()>),
+    Signal(Signal),
+    ReapOrphans,
+    ExitWhenStopped(oneshot::Sender<i32>),
 }
 
 #[derive(Debug)]
@@ -70,70 +98,369 @@ pub enum ProcessEvent {
     Started(String, String),
     Stopped(String, String),
     Restarted(String, String),
+    HealthChanged(Box<Service>, String, Health),
     AllStarted(String),
     AllStopped(String),
     AllRestarted(String),
 }
 
+// exit of a supervised child, reaped by the task waiting for it
+struct Exit {
+    service: Service,
+    project: String,
+    pid: u32,
+    // unknown for the services adopted from a previous daemon, which are not our children
+    status: Option<ExitStatus>,
+    exited_at: DateTime<Utc>,
+}
+
+// timers of the engine, handled once their delay has elapsed
+enum Timer {
+    // start again a crashed service
+    Backoff(Service, String),
+    // kill the process group of a service still alive after its stop timeout
+    StopTimeout(String, i32),
+    // check again whether the process group of a stopped service has exited
+    GroupExit(Service, String, i32),
+    // the dependencies of a waiting service may have timed out
+    DependencyTimeout,
+}
+
+// service waiting for its dependency conditions to be met
+struct Waiting {
+    service: Service,
+    project: String,
+    dependencies: Vec<(String, Dependency)>,
+    since: DateTime<Utc>,
+    started_at: Instant,
+}
+
 struct SuperviseurInternal {
-    commands: Arc<Mutex<mpsc::UnboundedReceiver<SuperviseurCommand>>>,
+    commands: mpsc::UnboundedReceiver<SuperviseurCommand>,
     cmd_tx: mpsc::UnboundedSender<SuperviseurCommand>,
     events: mpsc::UnboundedReceiver<ProcessEvent>,
     event_tx: mpsc::UnboundedSender<ProcessEvent>,
-    processes: Arc<Mutex<Vec<(Process, String)>>>,
+    registry: Arc<Mutex<Registry>>,
     childs: Arc<Mutex<HashMap<String, i32>>>,
-    config_map: Arc<Mutex<Vec<(ConfigurationData, String)>>>,
+    // services being stopped, with whether they should be started again once exited
+    stop_requests: HashMap<String, bool>,
+    // number of consecutive restarts of services exiting before `start_seconds`
+    restart_attempts: HashMap<String, u32>,
+    // dependents to restart once a restarted service has been started again
+    pending_cascades: HashMap<String, Vec<Service>>,
+    // replies of the start and restart commands, sent once the service is spawned or failed
+    pending_starts: HashMap<String, Vec<Reply>>,
+    // replies of the stop commands, sent once the service has exited
+    pending_stops: HashMap<String, Vec<Reply>>,
+    // services waiting for their dependencies
+    waiting: HashMap<String, Waiting>,
+    exits: JoinSet<Exit>,
+    timers: JoinSet<Timer>,
+    // last state written to the state file
+    saved_state: String,
+    // the state file is no longer written once the daemon is shutting down, so that the
+    // next daemon starts again the services stopped by the shutdown
+    shutting_down: bool,
+    // whether the state of the previous daemon is restored and the state saved, which
+    // is not the case in the foreground mode
+    persist: bool,
+    // projects being restarted, which are stopped before being started again
+    restarting: HashSet<String>,
+    // exit code of the services whose last exit was a failure
+    failures: HashMap<String, i32>,
+    // reply sent with the exit code of the daemon once all the services have stopped
+    exit_when_stopped: Option<oneshot::Sender<i32>>,
 }
 
 impl SuperviseurInternal {
     pub fn new(
-        commands: Arc<Mutex<mpsc::UnboundedReceiver<SuperviseurCommand>>>,
+        commands: mpsc::UnboundedReceiver<SuperviseurCommand>,
         cmd_tx: mpsc::UnboundedSender<SuperviseurCommand>,
         event_tx: mpsc::UnboundedSender<ProcessEvent>,
         events: mpsc::UnboundedReceiver<ProcessEvent>,
-        processes: Arc<Mutex<Vec<(Process, String)>>>,
-        config_map: Arc<Mutex<HashMap<String, ConfigurationData>>>,
+        registry: Arc<Mutex<Registry>>,
+        persist: bool,
     ) -> Self {
-        let config_map = Arc::new(Mutex::new(
-            config_map
-                .lock()
-                .unwrap()
-                .iter()
-                .map(|(_, v)| (v.clone(), v.project.clone()))
-                .collect(),
-        ));
         Self {
             commands,
             events,
             event_tx,
             cmd_tx,
-            processes,
+            registry,
             childs: Arc::new(Mutex::new(HashMap::new())),
-            config_map,
+            stop_requests: HashMap::new(),
+            restart_attempts: HashMap::new(),
+            pending_cascades: HashMap::new(),
+            pending_starts: HashMap::new(),
+            pending_stops: HashMap::new(),
+            waiting: HashMap::new(),
+            exits: JoinSet::new(),
+            timers: JoinSet::new(),
+            saved_state: String::new(),
+            shutting_down: false,
+            persist,
+            restarting: HashSet::new(),
+            failures: HashMap::new(),
+            exit_when_stopped: None,
+        }
+    }
+
+    async fn run(mut self) {
+        if self.persist {
+            self.restore_state();
+        }
+        loop {
+            tokio::select! {
+                cmd = self.commands.recv() => match cmd {
+                    Some(cmd) => {
+                        if let Err(e) = self.handle_command(cmd) {
+                            println!("{:?}", e);
+                        }
+                    }
+                    None => return, // client has disconnected - shut down.
+                },
+                Some(event) = self.events.recv() => {
+                    if let Err(e) = self.handle_event(event) {
+                        println!("{:?}", e);
+                    }
+                }
+                Some(exit) = self.exits.join_next() => match exit {
+                    Ok(exit) => {
+                        if let Err(e) = self.handle_exit(exit) {
+                            println!("{:?}", e);
+                        }
+                    }
+                    Err(e) => println!("{:?}", e),
+                },
+                Some(timer) = self.timers.join_next() => match timer {
+                    Ok(timer) => {
+                        if let Err(e) = self.handle_timer(timer) {
+                            println!("{:?}", e);
+                        }
+                    }
+                    Err(e) => println!("{:?}", e),
+                },
+            }
+            self.check_waiting();
+            self.check_stopped();
+            self.save_state();
         }
     }
 
+    // loads the projects of the previous daemon again and adopts the services it left running
+    fn restore_state(&mut self) {
+        let state = match DaemonState::load() {
+            Ok(Some(state)) => state,
+            Ok(None) => return,
+            Err(e) => {
+                println!("Cannot read the daemon state: {}", e);
+                return;
+            }
+        };
+
+        for saved in state.projects {
+            let mut config = saved.config;
+            let project = config.project.clone();
+            if let Err(e) = config.resolve_dependencies() {
+                println!("Cannot restore {}: {}", saved.path, e);
+                continue;
+            }
+            self.registry
+                .lock()
+                .unwrap()
+                .insert_config(&saved.path, config.clone());
+            for service in &config.services {
+                if let Err(e) = self.handle_watch_for_changes(
+                    service.working_dir.clone(),
+                    service.clone(),
+                    project.clone(),
+                ) {
+                    println!("{:?}", e);
+                }
+            }
+            if let Err(e) = self.handle_load_config(config.clone(), project.clone()) {
+                println!("{:?}", e);
+                continue;
+            }
+
+            for service_state in saved.services {
+                let service = match config
+                    .services
+                    .iter()
+                    .find(|s| s.id.as_ref() == Some(&service_state.id))
+                {
+                    Some(service) => service.clone(),
+                    None => continue,
+                };
+                if let Some(process) = self
+                    .registry
+                    .lock()
+                    .unwrap()
+                    .process_mut(&project, &service_state.id)
+                {
+                    process.restarts = service_state.restarts;
+                    process.stopped_by_user = service_state.stopped_by_user;
+                }
+                // as in docker, a service stopped by the user is started again along with
+                // the daemon if it should always run, but not if it is `unless-stopped`
+                if service_state.stopped_by_user {
+                    if service.get_restart_policy() == RestartPolicy::Always {
+                        if let Err(e) = self.handle_start(service, project.clone(), None) {
+                            println!("{:?}", e);
+                        }
+                    }
+                    continue;
+                }
+                if service_state.desired_state != DesiredState::Running {
+                    continue;
+                }
+
+                match (service_state.pid, service_state.started_at()) {
+                    (Some(pid), Some(started_at))
+                        if state::is_same_process(pid, &service.command, started_at) =>
+                    {
+                        println!("Adopted {} (pid {})", service.name, pid);
+                        self.adopt(service, project.clone(), pid, started_at);
+                    }
+                    // exited while the daemon was down
+                    _ => {
+                        if service.get_restart_policy() == RestartPolicy::No {
+                            continue;
+                        }
+                        if let Err(e) = self.handle_start(service, project.clone(), None) {
+                            println!("{:?}", e);
+                        }
+                    }
+                }
+            }
+
+            self.autostart(&config);
+        }
+    }
+
+    // brings up the services marked `autostart`, their dependencies first, except the
+    // ones the user has stopped
+    fn autostart(&mut self, config: &ConfigurationData) {
+        let graph = match DependencyGraph::new(config.services.clone()) {
+            Ok(graph) => graph,
+            Err(e) => {
+                println!("Cannot autostart {}: {}", config.project, e);
+                return;
+            }
+        };
+        for layer in graph.start_layers() {
+            for service in layer.into_iter().filter(|s| s.autostart) {
+                if self.is_stopped_by_user(&service, &config.project) {
+                    continue;
+                }
+                if let Err(e) = self.handle_start(service, config.project.clone(), None) {
+                    println!("{:?}", e);
+                }
+            }
+        }
+    }
+
+    // saves the state of the daemon, if it changed since it was last saved
+    fn save_state(&mut self) {
+        if !self.persist || self.shutting_down {
+            return;
+        }
+        let state = DaemonState::from(&*self.registry.lock().unwrap());
+        let content = match serde_json::to_string_pretty(&state) {
+            Ok(content) => content,
+            Err(e) => {
+                println!("Cannot serialize the daemon state: {}", e);
+                return;
+            }
+        };
+        if content == self.saved_state {
+            return;
+        }
+        if let Err(e) = DaemonState::save(&content) {
+            println!("Cannot save the daemon state: {}", e);
+        }
+        self.saved_state = content;
+    }
+
+    // supervises a service spawned by a previous daemon, which is still running
+    fn adopt(&mut self, service: Service, project: String, pid: u32, started_at: DateTime<Utc>) {
+        let service_key = format!("{}-{}", project, service.name);
+        if let Some(process) = self
+            .registry
+            .lock()
+            .unwrap()
+            .process_mut(&project, service_id(&service))
+        {
+            process.pid = Some(pid);
+            process.state = State::Running;
+            process.health = match service.healthcheck {
+                Some(_) => Health::Starting,
+                None => Health::Unknown,
+            };
+            process.up_time = Some(started_at);
+        }
+        self.childs
+            .lock()
+            .unwrap()
+            .insert(service_key.clone(), pid as i32);
+        self.check_health(&service, &project, pid);
+
+        // the service still writes its output to the named pipes it was spawned with
+        match reopen_output(&service) {
+            Ok(output) => {
+                tokio::spawn(capture_output(service.clone(), pid, output));
+            }
+            Err(e) => println!("Cannot capture the output of {}: {}", service.name, e),
+        }
+
+        // not our child, so it can't be waited for, only polled
+        self.exits.spawn(async move {
+            while signal::kill(Pid::from_raw(pid as i32), None).is_ok() {
+                tokio::time::sleep(Duration::from_millis(500)).await;
+            }
+            Exit {
+                service,
+                project,
+                pid,
+                status: None,
+                exited_at: Utc::now(),
+            }
+        });
+    }
+
+    fn check_health(&self, service: &Service, project: &str, pid: u32) {
+        if service.healthcheck.is_none() {
+            return;
+        }
+        let childs = self.childs.clone();
+        let service_key = format!("{}-{}", project, service.name);
+        HealthChecker::new(
+            service.clone(),
+            project.to_string(),
+            self.event_tx.clone(),
+            move || childs.lock().unwrap().get(&service_key) == Some(&(pid as i32)),
+        );
+    }
+
+    // the configuration itself is already in the registry, only its processes are added
     pub fn handle_load_config(
         &mut self,
         cfg: ConfigurationData,
         project: String,
     ) -> Result<(), Error> {
-        let mut config_map = self.config_map.lock().unwrap();
-        config_map.retain(|(_, key)| *key != project);
-        config_map.push((cfg.clone(), project.clone()));
+        for service in cfg.services {
+            self.handle_load(service, project.clone())?;
+        }
         Ok(())
     }
 
     fn handle_load(&self, service: Service, project: String) -> Result<(), Error> {
-        let mut processes = self.processes.lock().unwrap();
+        let mut registry = self.registry.lock().unwrap();
+        let service_id = service.id.unwrap_or("-".to_string());
 
         // update and skip if already loaded
-        if let Some(process) = processes
-            .iter_mut()
-            .find(|(p, key)| p.name == service.name && key == &project)
-            .map(|(p, _)| p)
-        {
-            process.service_id = service.id.unwrap_or("-".to_string());
+        if let Some(process) = registry.process_mut(&project, &service_id) {
+            process.service_id = service_id;
             process.name = service.name;
             process.command = service.command;
             process.description = service.description;
@@ -144,12 +471,14 @@ impl SuperviseurInternal {
             process.auto_restart = service.autorestart;
             process.stdout = service.stdout;
             process.stderr = service.stderr;
+            process.port = Some(service.port);
             return Ok(());
         }
 
-        processes.push((
+        registry.insert_process(
+            &project,
             Process {
-                service_id: service.id.unwrap_or("-".to_string()),
+                service_id,
                 name: service.name,
                 command: service.command,
                 description: service.description,
@@ -161,172 +490,561 @@ impl SuperviseurInternal {
                 cpu: None,
                 mem: None,
                 up_time: None,
-                port: None,
+                port: Some(service.port),
                 env: service.env,
                 project: project.clone(),
                 r#type: service.r#type,
                 auto_restart: service.autorestart,
                 stdout: service.stdout,
                 stderr: service.stderr,
+                ..Default::default()
             },
-            project,
-        ));
+        );
         Ok(())
     }
 
-    fn handle_start(&mut self, service: Service, project: String) -> Result<(), Error> {
-        // start recursively if service depends on other services
-        let dependencies = service.depends_on.clone();
-        let dependencies = dependencies
+    fn handle_start(
+        &mut self,
+        service: Service,
+        project: String,
+        reply: Option<Reply>,
+    ) -> Result<(), Error> {
+        let service_key = format!("{}-{}", project, service.name);
+        self.restart_attempts.remove(&service_key);
+        if let Some(reply) = reply {
+            self.pending_starts
+                .entry(service_key.clone())
+                .or_default()
+                .push(reply);
+        }
+        let result = self.start_service(service, project);
+        if let Err(e) = &result {
+            self.reply_start(&service_key, CommandOutcome::Failed(e.to_string()));
+        }
+        result
+    }
+
+    fn start_service(&mut self, service: Service, project: String) -> Result<(), Error> {
+        let service_key = format!("{}-{}", project, service.name);
+        let pid = self.childs.lock().unwrap().get(&service_key).copied();
+        if let Some(pid) = pid {
+            self.reply_start(&service_key, CommandOutcome::AlreadyRunning(pid as u32));
+            return Ok(());
+        }
+        // the reply is sent once the dependencies are ready
+        if self.get_state(&service, &project) == Some(State::Waiting) {
+            return Ok(());
+        }
+
+        let dependencies = service
+            .depends_on
+            .dependencies()
             .into_iter()
-            .filter(|d| d != &service.name)
-            .collect::<Vec<String>>();
-        if dependencies.len() > 0 {
-            let config_map = self.config_map.lock().unwrap();
-            let config = config_map
-                .iter()
-                .find(|(_, key)| *key == project)
-                .map(|(c, _)| c)
+            .filter(|(name, _)| name != &service.name)
+            .collect::<Vec<(String, Dependency)>>();
+        if dependencies.is_empty() {
+            return self.spawn_service(service, project).map(|_| ());
+        }
+
+        // start the dependencies, the service is spawned once their conditions are met
+        let services = {
+            let registry = self.registry.lock().unwrap();
+            let config = registry
+                .config(&project)
                 .ok_or(anyhow::anyhow!("Project {} not found", project))?;
-            let services = config.services.clone();
-            for dependency in dependencies.into_iter() {
-                match services.iter().find(|s| s.name == dependency) {
-                    Some(s) => {
-                        self.cmd_tx
-                            .send(SuperviseurCommand::Start(s.clone(), project.clone()))
-                            .unwrap();
-                        thread::sleep(Duration::from_millis(100));
-                    }
+            let mut services = vec![];
+            for (name, _) in &dependencies {
+                match config.services.iter().find(|s| &s.name == name) {
+                    Some(s) => services.push(s.clone()),
                     None => {
-                        return Err(anyhow::anyhow!("Service {} not found", dependency));
+                        return Err(anyhow::anyhow!("Service {} not found", name));
                     }
                 }
             }
+            services
+        };
+
+        // an exit of the dependencies counts from now on
+        let since = Utc::now();
+        for s in services {
+            if let Err(e) = self.handle_start(s, project.clone(), None) {
+                println!("{:?}", e);
+            }
         }
 
-        // skip if already started
-        let mut processes = self.processes.lock().unwrap();
-        if let Some(process) = processes
+        let mut timeouts = dependencies
             .iter()
-            .find(|(p, key)| p.name == service.name && key == &project)
-            .map(|(p, _)| p)
-        {
-            if process.state == State::Running {
-                return Ok(());
+            .map(|(_, d)| d.get_timeout())
+            .collect::<Vec<Duration>>();
+        // one timer per distinct timeout
+        timeouts.sort_unstable();
+        timeouts.dedup();
+        for timeout in timeouts {
+            self.timers.spawn(async move {
+                tokio::time::sleep(timeout).await;
+                Timer::DependencyTimeout
+            });
+        }
+
+        self.set_state(&service, &project, State::Waiting);
+        self.waiting.insert(
+            service_key,
+            Waiting {
+                service,
+                project,
+                dependencies,
+                since,
+                started_at: Instant::now(),
+            },
+        );
+        Ok(())
+    }
+
+    fn handle_start_all(&mut self, project: String, reply: Option<ReplyAll>) -> Result<(), Error> {
+        self.restarting.remove(&project);
+        let services = self
+            .registry
+            .lock()
+            .unwrap()
+            .config(&project)
+            .map(|c| c.services.clone())
+            .ok_or(anyhow::anyhow!("Project {} not found", project))?;
+
+        // start layer by layer, the services of a layer wait for their dependencies
+        // in the previous layers
+        let graph = DependencyGraph::new(services)?;
+        let mut outcomes = vec![];
+        for layer in graph.start_layers() {
+            for service in layer {
+                let (reply_tx, reply_rx) = oneshot::channel();
+                outcomes.push((service.name.clone(), reply_rx));
+                if let Err(e) = self.handle_start(service, project.clone(), Some(reply_tx)) {
+                    println!("{:?}", e);
+                }
             }
         }
 
-        let envs = service.env.clone();
-        let working_dir = service.working_dir.clone();
-        let mut child = std::process::Command::new("sh")
+        if let Some(reply) = reply {
+            tokio::spawn(async move {
+                let _ = reply.send(collect_outcomes(outcomes).await);
+            });
+        }
+        Ok(())
+    }
+
+    fn spawn_child(&self, service: &Service) -> Result<(Child, Output), Error> {
+        // open the log files first, so that a wrong path doesn't leave an unsupervised child
+        let (log_file, err_file) = open_log_files(service)?;
+        let id = service_id(service);
+        let create = |stream| {
+            fifo::create(id, stream).map_err(|e| {
+                anyhow::anyhow!(
+                    "Cannot create the {} pipe of {}: {}",
+                    stream,
+                    service.name,
+                    e
+                )
+            })
+        };
+        let (stdout, stdout_sender) = create(OutputStream::Stdout)?;
+        let (stderr, stderr_sender) = create(OutputStream::Stderr)?;
+
+        // run the service in its own process group, so that stopping it also
+        // stops the processes spawned by `sh -c`
+        let mut command = std::process::Command::new("sh");
+        command
             .arg("-c")
             .arg(&service.command)
-            .current_dir(working_dir)
-            .envs(envs)
-            .stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::piped())
+            .current_dir(&service.working_dir)
+            .envs(service.env.clone())
+            .process_group(0)
+            .stdout(stdout_sender)
+            .stderr(stderr_sender);
+        let child = tokio::process::Command::from(command)
             .spawn()
-            .unwrap();
+            .map_err(|e| {
+                anyhow::anyhow!(
+                    "Cannot start {} in {}: {}",
+                    service.name,
+                    service.working_dir,
+                    e
+                )
+            })?;
+        Ok((
+            child,
+            Output {
+                stdout,
+                stderr,
+                log_file,
+                err_file,
+            },
+        ))
+    }
 
-        let mut process = &mut processes
-            .iter_mut()
-            .find(|(p, key)| p.name == service.name && key == &project)
-            .unwrap()
-            .0;
-        process.pid = Some(child.id());
+    fn spawn_service(&mut self, service: Service, project: String) -> Result<u32, Error> {
+        let service_key = format!("{}-{}", project, service.name);
+        let spawned = match self.get_state(&service, &project) {
+            Some(_) => self.spawn_child(&service),
+            None => Err(anyhow::anyhow!("Service {} not found", service.name)),
+        };
+        let (mut child, output) = match spawned {
+            Ok(spawned) => spawned,
+            Err(e) => {
+                self.set_failed(&service, &project, &e.to_string());
+                return Err(e);
+            }
+        };
+
+        // the pid is known until the child has been reaped
+        let pid = child.id().unwrap_or_default();
+        {
+            let mut registry = self.registry.lock().unwrap();
+            let process = registry
+                .process_mut(&project, service_id(&service))
+                .unwrap();
+            process.pid = Some(pid);
+            process.state = State::Running;
+            process.error = None;
+            process.stopped_by_user = false;
+            process.health = match service.healthcheck {
+                Some(_) => Health::Starting,
+                None => Health::Unknown,
+            };
+            process.up_time = Some(chrono::Utc::now());
+        }
         self.event_tx
             .send(ProcessEvent::Started(service.name.clone(), project.clone()))
             .unwrap();
 
-        process.up_time = Some(chrono::Utc::now());
-        let service_key = format!("{}-{}", project, service.name);
         self.childs
             .lock()
             .unwrap()
-            .insert(service_key, child.id() as i32);
-
-        let stdout = child.stdout.take().unwrap();
-        let stderr = child.stderr.take().unwrap();
-
-        let cloned_service = service.clone();
-
-        thread::spawn(move || {
-            let service = cloned_service;
-            let id = service.id.unwrap_or("-".to_string());
-            // write stdout to file
-            let mut log_file = std::fs::File::create(service.stdout).unwrap();
-
-            let stdout = std::io::BufReader::new(stdout);
-            for line in stdout.lines() {
-                let line = line.unwrap();
-                let line = format!("{}\n", line);
-                SimpleBroker::publish(TailLogStream {
-                    id: id.clone(),
-                    line: line.clone(),
-                });
-                SimpleBroker::publish(LogStream {
-                    id: id.clone(),
-                    line: line.clone(),
-                });
-                log_file.write_all(line.as_bytes()).unwrap();
+            .insert(service_key.clone(), pid as i32);
+        self.failures.remove(&service_key);
+        self.reply_start(&service_key, CommandOutcome::Started(pid));
+
+        // restart the dependents of a restarted service, they wait for its dependency
+        // condition to be met again
+        if let Some(dependents) = self.pending_cascades.remove(&service_key) {
+            for dependent in dependents {
+                if let Err(e) = self.handle_restart(dependent, project.clone(), None) {
+                    println!("{:?}", e);
+                }
             }
+        }
 
-            // write stderr to file
-            let mut err_file = std::fs::File::create(service.stderr).unwrap();
-            let stderr = std::io::BufReader::new(stderr);
-            for line in stderr.lines() {
-                let line = line.unwrap();
-                err_file.write_all(line.as_bytes()).unwrap();
+        self.check_health(&service, &project, pid);
+
+        tokio::spawn(capture_output(service.clone(), pid, output));
+
+        self.exits.spawn(async move {
+            let status = match child.wait().await {
+                Ok(status) => Some(status),
+                Err(e) => {
+                    println!("Cannot wait for {}: {}", service.name, e);
+                    None
+                }
+            };
+            Exit {
+                service,
+                project,
+                pid,
+                status,
+                exited_at: Utc::now(),
             }
         });
 
-        let cmd_tx = self.cmd_tx.clone();
-        let event_tx = self.event_tx.clone();
-        thread::spawn(move || {
-            let _status = child.wait().unwrap();
-            // println!("child exited with status: {}", status);
-            if service.autorestart {
-                cmd_tx
-                    .send(SuperviseurCommand::Start(service.clone(), project.clone()))
-                    .unwrap();
+        Ok(pid)
+    }
 
-                event_tx
-                    .send(ProcessEvent::Restarted(service.name, project))
-                    .unwrap();
-                return;
+    fn handle_exit(&mut self, exit: Exit) -> Result<(), Error> {
+        let Exit {
+            service,
+            project,
+            pid,
+            status,
+            exited_at,
+        } = exit;
+        let service_key = format!("{}-{}", project, service.name);
+        let stop_requested = self.stop_requests.contains_key(&service_key);
+
+        if let Some(process) = self
+            .registry
+            .lock()
+            .unwrap()
+            .process_mut(&project, service_id(&service))
+        {
+            process.exit_code = status.and_then(|s| s.code());
+            process.exit_signal =
+                status
+                    .and_then(|s| s.signal())
+                    .map(|signal| match Signal::try_from(signal) {
+                        Ok(signal) => signal.to_string(),
+                        Err(_) => signal.to_string(),
+                    });
+            process.exited_at = Some(exited_at);
+            process.health = Health::Unknown;
+            if stop_requested {
+                process.state = State::Stopped;
             }
-            event_tx
-                .send(ProcessEvent::Stopped(service.name, project))
+        }
+
+        {
+            let mut childs = self.childs.lock().unwrap();
+            if childs.get(&service_key) == Some(&(pid as i32)) {
+                childs.remove(&service_key);
+            }
+        }
+
+        // the child was stopped on purpose, don't restart it,
+        // unless it is stopped to be restarted
+        if stop_requested {
+            self.event_tx
+                .send(ProcessEvent::Stopped(service.name.clone(), project.clone()))
                 .unwrap();
-        });
+            self.finish_stop(service, project, pid as i32);
+            return Ok(());
+        }
+
+        self.handle_exited(service, project, status, exited_at)
+    }
+
+    // the service is started again once all the processes of its group have exited
+    fn finish_stop(&mut self, service: Service, project: String, pid: i32) {
+        if signal::killpg(Pid::from_raw(pid), None).is_ok() {
+            self.timers.spawn(async move {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                Timer::GroupExit(service, project, pid)
+            });
+            return;
+        }
 
+        let service_key = format!("{}-{}", project, service.name);
+        let restart = self.stop_requests.remove(&service_key).unwrap_or(false);
+        if restart {
+            if let Err(e) = self.handle_start(service, project, None) {
+                println!("{:?}", e);
+            }
+        }
+    }
+
+    fn handle_stop(
+        &mut self,
+        service: Service,
+        project: String,
+        restart: bool,
+        reply: Option<Reply>,
+    ) -> Result<(), Error> {
+        let service_key = format!("{}-{}", project, service.name);
+        if !restart {
+            self.set_stopped_by_user(&service, &project, true);
+        }
+        if let Some(reply) = reply {
+            self.pending_stops
+                .entry(service_key.clone())
+                .or_default()
+                .push(reply);
+        }
+        let result = self.stop_service(service, project, restart);
+        if let Err(e) = &result {
+            self.reply_stop(&service_key, CommandOutcome::Failed(e.to_string()));
+        }
+        result
+    }
+
+    fn stop_service(
+        &mut self,
+        service: Service,
+        project: String,
+        restart: bool,
+    ) -> Result<(), Error> {
+        let service_key = format!("{}-{}", project, service.name);
+        let pid = self.childs.lock().unwrap().get(&service_key).copied();
+        let pid = match pid {
+            Some(pid) => pid,
+            None => {
+                // cancel the pending restart of a crashed service, or the pending start
+                // of a service waiting for its dependencies
+                match self.get_state(&service, &project) {
+                    Some(State::Backoff | State::Waiting) => {
+                        self.set_state(&service, &project, State::Stopped);
+                        self.reply_start(&service_key, CommandOutcome::Stopped);
+                        self.event_tx
+                            .send(ProcessEvent::Stopped(service.name, project))
+                            .unwrap();
+                    }
+                    _ => self.reply_stop(&service_key, CommandOutcome::NotRunning),
+                }
+                return Ok(());
+            }
+        };
+
+        let stop_signal = service.get_stop_signal()?;
+        let stop_timeout = service.get_stop_timeout();
+
+        // the service is marked as stopped once the child has exited
+        self.stop_requests.insert(service_key.clone(), restart);
+        signal::killpg(Pid::from_raw(pid), stop_signal)?;
+
+        self.timers.spawn(async move {
+            tokio::time::sleep(stop_timeout).await;
+            Timer::StopTimeout(service_key, pid)
+        });
         Ok(())
     }
 
-    fn handle_stop(&self, service: Service, project: String, restart: bool) -> Result<(), Error> {
-        let mut childs = self.childs.lock().unwrap();
-        let service_key = format!("{}-{}", project.clone(), service.name.clone());
-        match childs.get(&service_key) {
-            Some(pid) => {
-                signal::kill(Pid::from_raw(*pid), Signal::SIGTERM)?;
-                childs.remove(&service_key);
+    fn handle_restart(
+        &mut self,
+        service: Service,
+        project: String,
+        reply: Option<Reply>,
+    ) -> Result<(), Error> {
+        let service_key = format!("{}-{}", project, service.name);
+        // replied once started again, like a start command
+        if let Some(reply) = reply {
+            self.pending_starts
+                .entry(service_key.clone())
+                .or_default()
+                .push(reply);
+        }
+        // a service which isn't running is just started, unless it has been stopped by the
+        // user and is restarted by a change of its files or along with a dependency
+        if !self.childs.lock().unwrap().contains_key(&service_key) {
+            if self.is_stopped_by_user(&service, &project) {
+                self.reply_start(&service_key, CommandOutcome::NotRunning);
+                return Ok(());
+            }
+            return self.handle_start(service, project, None);
+        }
 
-                self.event_tx
-                    .send(ProcessEvent::Stopped(service.name.clone(), project.clone()))
-                    .unwrap();
-                if restart {
-                    self.cmd_tx
-                        .send(SuperviseurCommand::Start(service, project))
+        let dependents = self.get_cascading_dependents(&service, &project);
+        if !dependents.is_empty() {
+            self.pending_cascades
+                .insert(service_key.clone(), dependents);
+        }
+        let result = self.stop_service(service, project, true);
+        if let Err(e) = &result {
+            self.reply_start(&service_key, CommandOutcome::Failed(e.to_string()));
+        }
+        result
+    }
+
+    // services restarting along with the given one, through a dependency with `restart = true`
+    fn get_cascading_dependents(&self, service: &Service, project: &str) -> Vec<Service> {
+        let registry = self.registry.lock().unwrap();
+        let config = match registry.config(project) {
+            Some(config) => config,
+            None => return vec![],
+        };
+        config
+            .services
+            .iter()
+            .filter(|s| {
+                s.depends_on
+                    .dependencies()
+                    .iter()
+                    .any(|(name, d)| name == &service.name && d.restart == Some(true))
+            })
+            .cloned()
+            .collect()
+    }
+
+    fn handle_stop_all(
+        &mut self,
+        project: String,
+        restart: bool,
+        reply: Option<ReplyAll>,
+    ) -> Result<(), Error> {
+        let services = self
+            .registry
+            .lock()
+            .unwrap()
+            .config(&project)
+            .map(|c| c.services.clone())
+            .ok_or(anyhow::anyhow!("Project {} not found", project))?;
+        let layers = DependencyGraph::new(services)?.stop_layers();
+        if restart {
+            self.restarting.insert(project.clone());
+        }
+
+        let cmd_tx = self.cmd_tx.clone();
+        tokio::spawn(async move {
+            let mut outcomes = vec![];
+            for layer in layers {
+                let mut layer_outcomes = vec![];
+                for service in layer {
+                    let (reply_tx, reply_rx) = oneshot::channel();
+                    layer_outcomes.push((service.name.clone(), reply_rx));
+                    cmd_tx
+                        .send(SuperviseurCommand::Stop(
+                            service,
+                            project.clone(),
+                            Some(reply_tx),
+                        ))
                         .unwrap();
                 }
-                Ok(())
+                // wait for the layer to exit before stopping the services it depends on
+                outcomes.extend(collect_outcomes(layer_outcomes).await);
+            }
+            if restart {
+                cmd_tx
+                    .send(SuperviseurCommand::StartAll(project, reply))
+                    .unwrap();
+                return;
+            }
+            if let Some(reply) = reply {
+                let _ = reply.send(outcomes);
+            }
+        });
+        Ok(())
+    }
+
+    // stops the services of all the projects before the daemon exits, or leaves them
+    // running to be adopted by the next daemon
+    fn handle_shutdown(
+        &mut self,
+        keep_children: bool,
+        reply: oneshot::Sender<()>,
+    ) -> Result<(), Error> {
+        self.save_state();
+        self.shutting_down = true;
+        if keep_children {
+            let _ = reply.send(());
+            return Ok(());
+        }
+
+        let projects = self
+            .registry
+            .lock()
+            .unwrap()
+            .projects()
+            .map(|p| p.config.project.clone())
+            .collect::<Vec<String>>();
+        let mut stopped = vec![];
+        for project in projects {
+            let (reply_tx, reply_rx) = oneshot::channel();
+            match self.handle_stop_all(project, false, Some(reply_tx)) {
+                Ok(()) => stopped.push(reply_rx),
+                Err(e) => println!("{:?}", e),
             }
-            None => Ok(()),
         }
+        tokio::spawn(async move {
+            for reply_rx in stopped {
+                let _ = reply_rx.await;
+            }
+            let _ = reply.send(());
+        });
+        Ok(())
     }
 
-    fn handle_restart(&mut self, service: Service, project: String) -> Result<(), Error> {
-        self.handle_stop(service.clone(), project.clone(), true)
+    // forwards a signal received by the daemon to the process group of each service
+    fn handle_signal(&self, forwarded: Signal) -> Result<(), Error> {
+        for pid in self.childs.lock().unwrap().values() {
+            if let Err(e) = signal::killpg(Pid::from_raw(*pid), forwarded) {
+                println!("Cannot send {} to {}: {}", forwarded, pid, e);
+            }
+        }
+        Ok(())
     }
 
     fn handle_watch_for_changes(
@@ -335,48 +1053,348 @@ impl SuperviseurInternal {
         service: Service,
         project: String,
     ) -> Result<(), Error> {
-        let superviseur_tx = self.cmd_tx.clone();
-        thread::spawn(move || {
-            let _watcher = WatchForChanges::new(dir, superviseur_tx, service, project.clone());
-            loop {
-                thread::sleep(Duration::from_secs(5));
-            }
-        });
+        WatchForChanges::new(dir, self.cmd_tx.clone(), service, project);
         Ok(())
     }
 
     fn handle_command(&mut self, cmd: SuperviseurCommand) -> Result<(), Error> {
         match cmd {
-            SuperviseurCommand::Load(service, project) => self.handle_load(service, project),
-            SuperviseurCommand::Start(service, project) => self.handle_start(service, project),
-            SuperviseurCommand::Stop(service, project) => self.handle_stop(service, project, false),
-            SuperviseurCommand::Restart(service, project) => self.handle_restart(service, project),
-            SuperviseurCommand::LoadConfig(config, project) => {
-                self.handle_load_config(config, project)
+            SuperviseurCommand::Start(service, project, reply) => {
+                self.handle_start(service, project, reply)
+            }
+            SuperviseurCommand::StartAll(project, reply) => self.handle_start_all(project, reply),
+            SuperviseurCommand::Stop(service, project, reply) => {
+                self.handle_stop(service, project, false, reply)
+            }
+            SuperviseurCommand::StopAll(project, reply) => {
+                self.handle_stop_all(project, false, reply)
+            }
+            SuperviseurCommand::Restart(service, project, reply) => {
+                self.set_stopped_by_user(&service, &project, false);
+                self.handle_restart(service, project, reply)
+            }
+            SuperviseurCommand::RestartAll(project, reply) => {
+                self.handle_stop_all(project, true, reply)
+            }
+            SuperviseurCommand::LoadConfig(config, project, reply) => {
+                let result = self.handle_load_config(config, project);
+                if let Some(reply) = reply {
+                    let _ = reply.send(match &result {
+                        Ok(()) => CommandOutcome::Loaded,
+                        Err(e) => CommandOutcome::Failed(e.to_string()),
+                    });
+                }
+                result
             }
             SuperviseurCommand::WatchForChanges(dir, service, project) => {
                 self.handle_watch_for_changes(dir, service, project)
             }
+            SuperviseurCommand::Shutdown(keep_children, reply) => {
+                self.handle_shutdown(keep_children, reply)
+            }
+            SuperviseurCommand::Signal(forwarded) => self.handle_signal(forwarded),
+            SuperviseurCommand::ReapOrphans => {
+                let supervised = self.childs.lock().unwrap().values().copied().collect();
+                init::reap_orphans(&supervised);
+                Ok(())
+            }
+            SuperviseurCommand::ExitWhenStopped(reply) => {
+                self.exit_when_stopped = Some(reply);
+                Ok(())
+            }
+        }
+    }
+
+    fn get_state(&self, service: &Service, project: &str) -> Option<State> {
+        self.registry
+            .lock()
+            .unwrap()
+            .process(project, service_id(service))
+            .map(|p| p.state.clone())
+    }
+
+    fn reply_start(&mut self, service_key: &str, outcome: CommandOutcome) {
+        for reply in self.pending_starts.remove(service_key).unwrap_or_default() {
+            // the caller may have stopped waiting
+            let _ = reply.send(outcome.clone());
+        }
+    }
+
+    fn reply_stop(&mut self, service_key: &str, outcome: CommandOutcome) {
+        for reply in self.pending_stops.remove(service_key).unwrap_or_default() {
+            let _ = reply.send(outcome.clone());
+        }
+    }
+
+    fn set_failed(&mut self, service: &Service, project: &str, error: &str) {
+        if let Some(process) = self
+            .registry
+            .lock()
+            .unwrap()
+            .process_mut(project, service_id(service))
+        {
+            process.state = State::Failed;
+            process.error = Some(error.to_string());
+        }
+        self.failures
+            .insert(format!("{}-{}", project, service.name), 1);
+        self.reply_start(
+            &format!("{}-{}", project, service.name),
+            CommandOutcome::Failed(error.to_string()),
+        );
+        let mut payload = schema::objects::service::Service::from(service);
+        payload.status = String::from("FAILED");
+        SimpleBroker::publish(ServiceStopped { payload });
+    }
+
+    fn is_stopped_by_user(&self, service: &Service, project: &str) -> bool {
+        self.registry
+            .lock()
+            .unwrap()
+            .process(project, service_id(service))
+            .map(|p| p.stopped_by_user)
+            .unwrap_or(false)
+    }
+
+    fn set_stopped_by_user(&self, service: &Service, project: &str, stopped: bool) {
+        if let Some(process) = self
+            .registry
+            .lock()
+            .unwrap()
+            .process_mut(project, service_id(service))
+        {
+            process.stopped_by_user = stopped;
+        }
+    }
+
+    fn set_state(&self, service: &Service, project: &str, state: State) {
+        if let Some(process) = self
+            .registry
+            .lock()
+            .unwrap()
+            .process_mut(project, service_id(service))
+        {
+            process.state = state;
+        }
+    }
+
+    fn handle_exited(
+        &mut self,
+        service: Service,
+        project: String,
+        status: Option<ExitStatus>,
+        exited_at: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        let service_key = format!("{}-{}", project, service.name);
+        // the service has been started again in the meantime
+        if self.childs.lock().unwrap().contains_key(&service_key) {
+            return Ok(());
+        }
+
+        match status {
+            Some(status) if status.success() => {
+                self.failures.remove(&service_key);
+            }
+            // 128 + signal, like a shell
+            status => {
+                let code = status
+                    .and_then(|s| s.code().or_else(|| s.signal().map(|signal| 128 + signal)))
+                    .unwrap_or(1);
+                self.failures.insert(service_key.clone(), code);
+            }
+        }
+
+        let restart = match service.get_restart_policy() {
+            RestartPolicy::No => false,
+            // an unknown exit status counts as a failure
+            RestartPolicy::OnFailure => !status.map(|s| s.success()).unwrap_or(false),
+            // they only differ once the service has been stopped by the user, which
+            // doesn't go through here
+            RestartPolicy::Always | RestartPolicy::UnlessStopped => true,
+        };
+
+        if !restart {
+            self.restart_attempts.remove(&service_key);
+            self.set_state(&service, &project, State::Stopped);
+            self.event_tx
+                .send(ProcessEvent::Stopped(service.name, project))
+                .unwrap();
+            return Ok(());
+        }
+
+        // reset the crash loop detection once the service stayed up long enough
+        let up_time = self
+            .registry
+            .lock()
+            .unwrap()
+            .process(&project, service_id(&service))
+            .and_then(|p| p.up_time);
+        let stable = match up_time {
+            Some(up_time) => (exited_at - up_time)
+                .to_std()
+                .map(|up| up >= service.get_start_seconds())
+                .unwrap_or(false),
+            None => false,
+        };
+        let attempts = self.restart_attempts.entry(service_key).or_insert(0);
+        if stable {
+            *attempts = 0;
+        }
+        *attempts += 1;
+
+        let mut payload = schema::objects::service::Service::from(&service);
+        if *attempts > service.get_max_restarts() {
+            println!(
+                "{} exited too quickly {} times in a row, giving up",
+                service.name,
+                *attempts - 1
+            );
+            self.set_state(&service, &project, State::Fatal);
+            payload.status = String::from("FATAL");
+            SimpleBroker::publish(ServiceStopped { payload });
+            return Ok(());
+        }
+
+        let backoff = service.get_backoff(*attempts);
+        self.set_state(&service, &project, State::Backoff);
+        payload.status = String::from("BACKOFF");
+        SimpleBroker::publish(ServiceStopped { payload });
+
+        self.timers.spawn(async move {
+            tokio::time::sleep(backoff).await;
+            Timer::Backoff(service, project)
+        });
+        Ok(())
+    }
+
+    fn handle_backoff_elapsed(&mut self, service: Service, project: String) -> Result<(), Error> {
+        // the service has been stopped or started again in the meantime
+        if self.get_state(&service, &project) != Some(State::Backoff) {
+            return Ok(());
+        }
+        self.spawn_service(service.clone(), project.clone())?;
+        if let Some(process) = self
+            .registry
+            .lock()
+            .unwrap()
+            .process_mut(&project, service_id(&service))
+        {
+            process.restarts += 1;
+        }
+        self.event_tx
+            .send(ProcessEvent::Restarted(service.name, project))
+            .unwrap();
+        Ok(())
+    }
+
+    fn handle_timer(&mut self, timer: Timer) -> Result<(), Error> {
+        match timer {
+            Timer::Backoff(service, project) => self.handle_backoff_elapsed(service, project)?,
+            Timer::StopTimeout(service_key, pid) => {
+                // the service is alive as long as its child or any process of its group is
+                let alive = self.childs.lock().unwrap().get(&service_key) == Some(&pid)
+                    || signal::killpg(Pid::from_raw(pid), None).is_ok();
+                // still alive after the stop timeout, escalate to SIGKILL
+                if alive {
+                    signal::killpg(Pid::from_raw(pid), Signal::SIGKILL)?;
+                }
+            }
+            Timer::GroupExit(service, project, pid) => self.finish_stop(service, project, pid),
+            // checked along with the other waiting services
+            Timer::DependencyTimeout => {}
+        }
+        Ok(())
+    }
+
+    // replies with the exit code of the daemon once the services have run and all of
+    // them have stopped: 0, or the highest exit code of the services which failed
+    fn check_stopped(&mut self) {
+        if self.exit_when_stopped.is_none()
+            || !self.childs.lock().unwrap().is_empty()
+            || !self.waiting.is_empty()
+            || !self.stop_requests.is_empty()
+            || !self.restarting.is_empty()
+        {
+            return;
+        }
+        {
+            let registry = self.registry.lock().unwrap();
+            if registry.processes().any(|p| p.state == State::Backoff) {
+                return;
+            }
+            let has_run = registry
+                .processes()
+                .any(|p| p.exited_at.is_some() || p.error.is_some());
+            if !has_run {
+                return;
+            }
+        }
+        let code = self.failures.values().copied().max().unwrap_or(0);
+        if let Some(reply) = self.exit_when_stopped.take() {
+            let _ = reply.send(code);
+        }
+    }
+
+    // spawns the waiting services whose dependency conditions are met, and fails the
+    // ones whose dependencies failed or timed out
+    fn check_waiting(&mut self) {
+        loop {
+            let mut ready = vec![];
+            let mut failed = vec![];
+            {
+                let registry = self.registry.lock().unwrap();
+                // the service has been stopped in the meantime
+                self.waiting.retain(|_, w| {
+                    registry
+                        .process(&w.project, service_id(&w.service))
+                        .map(|p| p.state == State::Waiting)
+                        .unwrap_or(false)
+                });
+                for (service_key, w) in &self.waiting {
+                    let project = match registry.project(&w.project) {
+                        Some(project) => project,
+                        None => continue,
+                    };
+                    match dependencies_ready(
+                        project,
+                        &w.dependencies,
+                        w.since,
+                        w.started_at.elapsed(),
+                    ) {
+                        Ok(true) => ready.push(service_key.clone()),
+                        Ok(false) => {}
+                        Err(e) => failed.push((service_key.clone(), e.to_string())),
+                    }
+                }
+            }
+            if ready.is_empty() && failed.is_empty() {
+                return;
+            }
+
+            for (service_key, error) in failed {
+                if let Some(w) = self.waiting.remove(&service_key) {
+                    println!("Failed to start {}: {}", w.service.name, error);
+                    self.set_failed(&w.service, &w.project, &error);
+                }
+            }
+            // spawning a service may fulfill the conditions of other services
+            for service_key in ready {
+                if let Some(w) = self.waiting.remove(&service_key) {
+                    if let Err(e) = self.spawn_service(w.service, w.project) {
+                        println!("{:?}", e);
+                    }
+                }
+            }
         }
     }
 
     fn handle_event(&mut self, event: ProcessEvent) -> Result<(), Error> {
-        let mut processes = self.processes.lock().unwrap();
         match event {
             ProcessEvent::Started(service_name, project) => {
-                let mut process = &mut processes
-                    .iter_mut()
-                    .find(|(p, key)| p.name == service_name && key == &project)
-                    .unwrap()
-                    .0;
-                process.state = State::Running;
-
                 // call SimpleBroker::publish
-                let config_map = self.config_map.lock().unwrap();
-                let config = config_map
-                    .iter()
-                    .find(|(c, k)| k == &project)
-                    .map(|(c, _)| c)
+                let registry = self.registry.lock().unwrap();
+                let config = registry
+                    .config(&project)
                     .ok_or(anyhow::anyhow!("Config not found"))?;
                 let service = config
                     .services
@@ -390,19 +1408,14 @@ impl SuperviseurInternal {
                 });
             }
             ProcessEvent::Stopped(service_name, project) => {
-                let mut process = &mut processes
-                    .iter_mut()
-                    .find(|(p, key)| p.name == service_name && key == &project)
-                    .unwrap()
-                    .0;
-                process.state = State::Stopped;
-
+                self.reply_stop(
+                    &format!("{}-{}", project, service_name),
+                    CommandOutcome::Stopped,
+                );
                 // call SimpleBroker::publish
-                let config_map = self.config_map.lock().unwrap();
-                let config = config_map
-                    .iter()
-                    .find(|(c, k)| k == &project)
-                    .map(|(c, _)| c)
+                let registry = self.registry.lock().unwrap();
+                let config = registry
+                    .config(&project)
                     .ok_or(anyhow::anyhow!("Config not found"))?;
                 let service = config
                     .services
@@ -416,19 +1429,10 @@ impl SuperviseurInternal {
                 });
             }
             ProcessEvent::Restarted(service_name, project) => {
-                let mut process = &mut processes
-                    .iter_mut()
-                    .find(|(p, key)| p.name == service_name && key == &project)
-                    .unwrap()
-                    .0;
-                process.state = State::Running;
-
                 // call SimpleBroker::publish
-                let config_map = self.config_map.lock().unwrap();
-                let config = config_map
-                    .iter()
-                    .find(|(c, k)| k == &project)
-                    .map(|(c, _)| c)
+                let registry = self.registry.lock().unwrap();
+                let config = registry
+                    .config(&project)
                     .ok_or(anyhow::anyhow!("Config not found"))?;
                 let service = config
                     .services
@@ -441,13 +1445,28 @@ impl SuperviseurInternal {
                     payload: service.clone(),
                 });
             }
+            ProcessEvent::HealthChanged(service, project, health) => {
+                let mut registry = self.registry.lock().unwrap();
+                let process = match registry.process_mut(&project, service_id(&service)) {
+                    Some(process) => process,
+                    None => return Ok(()),
+                };
+                // the check raced with the exit of the service
+                if process.state != State::Running {
+                    return Ok(());
+                }
+                process.health = health.clone();
+
+                let mut service = schema::objects::service::Service::from(service.as_ref());
+                service.status = String::from("RUNNING");
+                service.health = health.to_string();
+                SimpleBroker::publish(ServiceHealthChanged { payload: service });
+            }
             ProcessEvent::AllStarted(project) => {
                 // call SimpleBroker::publish
-                let config_map = self.config_map.lock().unwrap();
-                let config = config_map
-                    .iter()
-                    .find(|(c, k)| k == &project)
-                    .map(|(c, _)| c)
+                let registry = self.registry.lock().unwrap();
+                let config = registry
+                    .config(&project)
                     .ok_or(anyhow::anyhow!("Config not found"))?;
                 let services = config
                     .services
@@ -458,11 +1477,9 @@ impl SuperviseurInternal {
             }
             ProcessEvent::AllRestarted(project) => {
                 // call SimpleBroker::publish
-                let config_map = self.config_map.lock().unwrap();
-                let config = config_map
-                    .iter()
-                    .find(|(c, k)| k == &project)
-                    .map(|(c, _)| c)
+                let registry = self.registry.lock().unwrap();
+                let config = registry
+                    .config(&project)
                     .ok_or(anyhow::anyhow!("Config not found"))?;
                 let services = config
                     .services
@@ -473,11 +1490,9 @@ impl SuperviseurInternal {
             }
             ProcessEvent::AllStopped(project) => {
                 // call SimpleBroker::publish
-                let config_map = self.config_map.lock().unwrap();
-                let config = config_map
-                    .iter()
-                    .find(|(c, k)| k == &project)
-                    .map(|(c, _)| c)
+                let registry = self.registry.lock().unwrap();
+                let config = registry
+                    .config(&project)
                     .ok_or(anyhow::anyhow!("Config not found"))?;
                 let services = config
                     .services
@@ -491,36 +1506,244 @@ impl SuperviseurInternal {
     }
 }
 
-impl Future for SuperviseurInternal {
-    type Output = ();
+fn dependencies_ready(
+    project: &Project,
+    dependencies: &[(String, Dependency)],
+    since: DateTime<Utc>,
+    elapsed: Duration,
+) -> Result<bool, Error> {
+    let mut ready = true;
+    for (name, dependency) in dependencies {
+        let process = project
+            .process_by_name(name)
+            .ok_or(anyhow::anyhow!("Service {} not found", name))?;
+        if dependency_ready(process, &dependency.condition, since)? {
+            continue;
+        }
+        if elapsed >= dependency.get_timeout() {
+            return Err(anyhow::anyhow!(
+                "timed out after {}s waiting for {} to be {}",
+                dependency.get_timeout().as_secs(),
+                name,
+                dependency.condition
+            ));
+        }
+        ready = false;
+    }
+    Ok(ready)
+}
 
-    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        loop {
-            let cmd = match self.commands.lock().unwrap().poll_recv(cx) {
-                Poll::Ready(Some(cmd)) => Some(cmd),
-                Poll::Ready(None) => return Poll::Ready(()), // client has disconnected - shut down.
-                _ => None,
-            };
+// id of a service of a registered configuration, assigned when it was loaded
+fn service_id(service: &Service) -> &str {
+    service.id.as_deref().unwrap_or("-")
+}
 
-            if let Some(cmd) = cmd {
-                if let Err(e) = self.handle_command(cmd) {
-                    println!("{:?}", e);
-                }
-            }
+fn dependency_ready(
+    process: &Process,
+    condition: &DependencyCondition,
+    since: DateTime<Utc>,
+) -> Result<bool, Error> {
+    if process.state == State::Fatal {
+        return Err(anyhow::anyhow!(
+            "dependency {} exited too quickly and was given up",
+            process.name
+        ));
+    }
+    if process.state == State::Failed {
+        return Err(anyhow::anyhow!(
+            "dependency {} failed to start: {}",
+            process.name,
+            process.error.clone().unwrap_or_default()
+        ));
+    }
+    match condition {
+        DependencyCondition::Started => Ok(process.state == State::Running),
+        DependencyCondition::Healthy => {
+            Ok(process.state == State::Running && process.health == Health::Healthy)
+        }
+        // only an exit that happened after the dependent was started counts
+        DependencyCondition::CompletedSuccessfully => match process.exited_at {
+            Some(exited_at) if exited_at >= since => match process.exit_code {
+                Some(0) => Ok(true),
+                Some(code) => Err(anyhow::anyhow!(
+                    "dependency {} exited with code {}",
+                    process.name,
+                    code
+                )),
+                None => match &process.exit_signal {
+                    Some(signal) => Err(anyhow::anyhow!(
+                        "dependency {} was killed by {}",
+                        process.name,
+                        signal
+                    )),
+                    None => Err(anyhow::anyhow!(
+                        "dependency {} exited with an unknown status",
+                        process.name
+                    )),
+                },
+            },
+            _ => Ok(false),
+        },
+    }
+}
 
-            let event = match self.events.poll_recv(cx) {
-                Poll::Ready(Some(event)) => Some(event),
-                Poll::Ready(None) => return Poll::Ready(()), // client has disconnected - shut down.
-                _ => None,
-            };
+// waits for the outcome of each service
+async fn collect_outcomes(
+    outcomes: Vec<(String, oneshot::Receiver<CommandOutcome>)>,
+) -> Vec<(String, CommandOutcome)> {
+    let mut collected = vec![];
+    for (name, reply) in outcomes {
+        let outcome = reply.await.unwrap_or_else(|_| {
+            CommandOutcome::Failed(String::from("The command has been dropped"))
+        });
+        collected.push((name, outcome));
+    }
+    collected
+}
 
-            if let Some(event) = event {
-                if let Err(e) = self.handle_event(event) {
-                    println!("{:?}", e);
-                }
+type SharedLogFile = Arc<Mutex<LogFile>>;
+
+// named pipes a service writes its output to, and log files the output is written to
+struct Output {
+    stdout: Receiver,
+    stderr: Receiver,
+    log_file: SharedLogFile,
+    err_file: SharedLogFile,
+}
+
+fn open_log_file(path: &str, service: &Service) -> Result<SharedLogFile, Error> {
+    let file = LogFile::open(path, service.log_rotation.clone())
+        .map_err(|e| anyhow::anyhow!("Cannot open log file {}: {}", path, e))?;
+    Ok(Arc::new(Mutex::new(file)))
+}
+
+fn open_log_files(service: &Service) -> Result<(SharedLogFile, SharedLogFile), Error> {
+    let log_file = open_log_file(&service.stdout, service)?;
+    // both streams may go to the same file, which must then be rotated only once
+    let err_file = match service.stderr == service.stdout {
+        true => log_file.clone(),
+        false => open_log_file(&service.stderr, service)?,
+    };
+    Ok((log_file, err_file))
+}
+
+// output of a service spawned by a previous daemon
+fn reopen_output(service: &Service) -> Result<Output, Error> {
+    let (log_file, err_file) = open_log_files(service)?;
+    let id = service_id(service);
+    Ok(Output {
+        stdout: fifo::open(id, OutputStream::Stdout)?,
+        stderr: fifo::open(id, OutputStream::Stderr)?,
+        log_file,
+        err_file,
+    })
+}
+
+// reads stdout and stderr concurrently, so that a service writing a lot to one of them
+// doesn't block on a full pipe while the other one is read
+async fn capture_output(service: Service, pid: u32, output: Output) {
+    let Output {
+        stdout,
+        stderr,
+        log_file,
+        err_file,
+    } = output;
+    tokio::join!(
+        capture_stream(&service, pid, OutputStream::Stdout, stdout, log_file),
+        capture_stream(&service, pid, OutputStream::Stderr, stderr, err_file),
+    );
+}
+
+// writes the lines of an output stream of a service to its log file, along with the
+// time they are received, and publishes them
+async fn capture_stream(
+    service: &Service,
+    pid: u32,
+    stream: OutputStream,
+    output: impl AsyncRead + Unpin,
+    file: SharedLogFile,
+) {
+    let id = service.id.as_deref().unwrap_or("-");
+    let path = match stream {
+        OutputStream::Stderr => &service.stderr,
+        _ => &service.stdout,
+    };
+    // keep reading the output when the log file can't be written anymore,
+    // otherwise the child would block on a full pipe
+    let mut write_failed = false;
+
+    let max_length = service.get_max_line_length();
+    let mut reader = BufReader::new(output);
+    // the bytes are logged as they are, only the published lines are lossily decoded
+    while let Ok(Some((bytes, partial))) = next_line(&mut reader, max_length).await {
+        let entry = LogEntry::received(&bytes, partial, stream, id, pid);
+        let line = format!("{}\n", entry.line);
+        SimpleBroker::publish(TailLogStream {
+            id: id.to_string(),
+            stream: stream.to_string(),
+            line: line.clone(),
+        });
+        SimpleBroker::publish(LogStream {
+            id: id.to_string(),
+            stream: stream.to_string(),
+            line,
+        });
+        let record = format!("{}\n", serde_json::to_string(&entry).unwrap());
+        let written = file.lock().unwrap().write_line(record.as_bytes());
+        if let Err(e) = written {
+            if !write_failed {
+                println!("Cannot write to {}: {}", path, e);
+                write_failed = true;
             }
+        }
+    }
+}
 
-            thread::sleep(Duration::from_millis(500));
+// reads the bytes of the next line of an output, without its newline, along with whether
+// the line has been cut at `max_length` bytes and goes on in the next one
+async fn next_line(
+    reader: &mut (impl AsyncBufRead + Unpin),
+    max_length: usize,
+) -> std::io::Result<Option<(Vec<u8>, bool)>> {
+    let mut line = vec![];
+    loop {
+        let available = reader.fill_buf().await?;
+        if available.is_empty() {
+            // the last line may have no newline
+            return Ok((!line.is_empty()).then_some((line, false)));
+        }
+        let wanted = &available[..available.len().min(max_length - line.len())];
+        if let Some(end) = wanted.iter().position(|b| *b == b'\n') {
+            line.extend_from_slice(&wanted[..end]);
+            reader.consume(end + 1);
+            return Ok(Some((line, false)));
+        }
+        let read = wanted.len();
+        line.extend_from_slice(wanted);
+        reader.consume(read);
+        if line.len() == max_length {
+            break;
         }
     }
+    // the line is complete if it ends right at the limit
+    let available = reader.fill_buf().await?;
+    if available.first() == Some(&b'\n') {
+        reader.consume(1);
+        return Ok(Some((line, false)));
+    }
+    Ok(Some((line, !available.is_empty())))
+}
+
+/// Sends a command to the engine and waits for its reply.
+pub async fn send_and_wait<T>(
+    cmd_tx: &mpsc::UnboundedSender<SuperviseurCommand>,
+    command: impl FnOnce(oneshot::Sender<T>) -> SuperviseurCommand,
+) -> Result<T, Error> {
+    let (reply_tx, reply_rx) = oneshot::channel();
+    cmd_tx
+        .send(command(reply_tx))
+        .map_err(|_| anyhow::anyhow!("The superviseur is not running"))?;
+    reply_rx
+        .await
+        .map_err(|_| anyhow::anyhow!("The command has been dropped"))
 }
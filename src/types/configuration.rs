@@ -1,9 +1,144 @@
+use anyhow::Error;
+use nix::sys::signal::Signal;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    path::PathBuf,
+    str::FromStr,
+    time::Duration,
+};
 
+pub const DEFAULT_STOP_TIMEOUT: u64 = 10;
+pub const DEFAULT_MAX_RESTARTS: u32 = 5;
+pub const DEFAULT_BACKOFF_SECONDS: u64 = 1;
+pub const DEFAULT_MAX_BACKOFF_SECONDS: u64 = 60;
+pub const DEFAULT_START_SECONDS: u64 = 1;
+pub const DEFAULT_HEALTHCHECK_INTERVAL: u64 = 10;
+pub const DEFAULT_HEALTHCHECK_TIMEOUT: u64 = 5;
+pub const DEFAULT_HEALTHCHECK_RETRIES: u32 = 3;
+pub const DEFAULT_DEPENDENCY_TIMEOUT: u64 = 60;
+pub const DEFAULT_LOG_MAX_FILES: u32 = 5;
+pub const DEFAULT_MAX_LINE_LENGTH: usize = 16384;
+
+/// Format of a Superfile, given by its extension.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub enum ConfigFormat {
     TOML,
+    #[default]
     HCL,
+    JSON,
+    YAML,
+}
+
+impl FromStr for ConfigFormat {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "" | "hcl" => Ok(ConfigFormat::HCL),
+            "toml" => Ok(ConfigFormat::TOML),
+            "json" => Ok(ConfigFormat::JSON),
+            "yaml" | "yml" => Ok(ConfigFormat::YAML),
+            _ => Err(Error::msg(format!(
+                "Unknown config format {}, expected hcl, toml, json or yaml",
+                s
+            ))),
+        }
+    }
+}
+
+impl Display for ConfigFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigFormat::TOML => write!(f, "toml"),
+            ConfigFormat::HCL => write!(f, "hcl"),
+            ConfigFormat::JSON => write!(f, "json"),
+            ConfigFormat::YAML => write!(f, "yaml"),
+        }
+    }
+}
+
+impl ConfigFormat {
+    /// Parses a Superfile written in this format. The errors tell the line and the
+    /// column where the Superfile is invalid, when the parser knows them.
+    pub fn parse(&self, content: &str) -> Result<ConfigurationData, Error> {
+        let (message, location) = match self {
+            ConfigFormat::HCL => match hcl::from_str(content) {
+                Ok(config) => return Ok(config),
+                Err(hcl::Error::Message { msg, location }) => {
+                    (msg, location.map(|l| (l.line, l.col)))
+                }
+                Err(e) => (e.to_string(), None),
+            },
+            ConfigFormat::TOML => match toml::from_str(content) {
+                Ok(config) => return Ok(config),
+                Err(e) => (
+                    e.message().to_string(),
+                    e.span().map(|span| line_and_column(content, span.start)),
+                ),
+            },
+            ConfigFormat::JSON => match serde_json::from_str(content) {
+                Ok(config) => return Ok(config),
+                Err(e) => (
+                    without_location(&e.to_string()),
+                    Some((e.line(), e.column())).filter(|(line, _)| *line > 0),
+                ),
+            },
+            ConfigFormat::YAML => match serde_yaml::from_str(content) {
+                Ok(config) => return Ok(config),
+                Err(e) => (
+                    without_location(&e.to_string()),
+                    e.location().map(|l| (l.line(), l.column())),
+                ),
+            },
+        };
+        let format = self.to_string().to_uppercase();
+        match location {
+            Some((line, column)) => Err(Error::msg(format!(
+                "Invalid {} Superfile at line {}, column {}: {}",
+                format, line, column, message
+            ))),
+            None => Err(Error::msg(format!(
+                "Invalid {} Superfile: {}",
+                format, message
+            ))),
+        }
+    }
+
+    /// Extensions of a Superfile in this format, the first one being used for a new one.
+    pub fn extensions(&self) -> &'static [&'static str] {
+        match self {
+            ConfigFormat::TOML => &["toml"],
+            ConfigFormat::HCL => &["hcl"],
+            ConfigFormat::JSON => &["json"],
+            ConfigFormat::YAML => &["yaml", "yml"],
+        }
+    }
+
+    pub fn serialize(&self, config: &ConfigurationData) -> Result<String, Error> {
+        match self {
+            ConfigFormat::HCL => Ok(hcl::to_string(config)?),
+            ConfigFormat::TOML => Ok(toml::to_string_pretty(config)?),
+            ConfigFormat::JSON => Ok(serde_json::to_string_pretty(config)?),
+            ConfigFormat::YAML => Ok(serde_yaml::to_string(config)?),
+        }
+    }
+}
+
+// one-based line and column of a byte offset
+fn line_and_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset.min(content.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.chars().rev().take_while(|c| *c != '\n').count() + 1;
+    (line, column)
+}
+
+// the message of a serde_json or serde_yaml error, which ends with its location
+fn without_location(message: &str) -> String {
+    match message.rsplit_once(" at line ") {
+        Some((message, _)) => message.to_string(),
+        None => message.to_string(),
+    }
 }
 
 pub struct Configuration {
@@ -11,7 +146,186 @@ pub struct Configuration {
     pub data: ConfigurationData,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestartPolicy {
+    No,
+    OnFailure,
+    Always,
+    UnlessStopped,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DependencyCondition {
+    #[default]
+    Started,
+    Healthy,
+    CompletedSuccessfully,
+}
+
+impl Display for DependencyCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DependencyCondition::Started => write!(f, "started"),
+            DependencyCondition::Healthy => write!(f, "healthy"),
+            DependencyCondition::CompletedSuccessfully => write!(f, "completed_successfully"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct Dependency {
+    #[serde(default)]
+    pub condition: DependencyCondition,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>, // seconds to wait for the condition before failing the start
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub restart: Option<bool>, // restart the dependent when the dependency is restarted
+}
+
+impl Dependency {
+    pub fn get_timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout.unwrap_or(DEFAULT_DEPENDENCY_TIMEOUT))
+    }
+}
+
+/// Either a list of service names, which only need to be started, or a map
+/// of service names to the condition to wait for, as in docker compose.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum DependsOn {
+    List(Vec<String>),
+    Map(BTreeMap<String, Dependency>),
+}
+
+impl Default for DependsOn {
+    fn default() -> Self {
+        DependsOn::List(vec![])
+    }
+}
+
+impl DependsOn {
+    pub fn names(&self) -> Vec<String> {
+        match self {
+            DependsOn::List(names) => names.clone(),
+            DependsOn::Map(dependencies) => dependencies.keys().cloned().collect(),
+        }
+    }
+
+    pub fn dependencies(&self) -> Vec<(String, Dependency)> {
+        match self {
+            DependsOn::List(names) => names
+                .iter()
+                .map(|name| (name.clone(), Dependency::default()))
+                .collect(),
+            DependsOn::Map(dependencies) => dependencies
+                .iter()
+                .map(|(name, dependency)| (name.clone(), dependency.clone()))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct HealthCheck {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>, // succeeds if the command exits with 0
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http: Option<String>, // succeeds if a GET on the url returns a 2xx or 3xx
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tcp: Option<String>, // succeeds if a connection can be opened to host:port or port
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interval: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_period: Option<u64>, // seconds during which failures are not counted
+}
+
+impl HealthCheck {
+    pub fn validate(&self) -> Result<(), Error> {
+        let probes = [&self.command, &self.http, &self.tcp]
+            .iter()
+            .filter(|p| p.is_some())
+            .count();
+        if probes > 1 {
+            return Err(Error::msg(
+                "A healthcheck accepts only one of command, http or tcp",
+            ));
+        }
+        if let Some(url) = &self.http {
+            if !url.starts_with("http://") {
+                return Err(Error::msg(format!(
+                    "Invalid healthcheck url: {}, only http:// urls are supported",
+                    url
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get_interval(&self) -> Duration {
+        Duration::from_secs(self.interval.unwrap_or(DEFAULT_HEALTHCHECK_INTERVAL))
+    }
+
+    pub fn get_timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout.unwrap_or(DEFAULT_HEALTHCHECK_TIMEOUT))
+    }
+
+    pub fn get_retries(&self) -> u32 {
+        self.retries.unwrap_or(DEFAULT_HEALTHCHECK_RETRIES)
+    }
+
+    pub fn get_start_period(&self) -> Duration {
+        Duration::from_secs(self.start_period.unwrap_or(0))
+    }
+}
+
+/// Rotation of the log files of a service. The current file is renamed `<file>.1`
+/// once it reaches `max_size` or `max_age`, the previous ones being shifted to
+/// `<file>.2`, `<file>.3`, ... up to `max_files`.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct LogRotation {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<u64>, // bytes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_age: Option<u64>, // seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_files: Option<u32>, // rotated files kept, the oldest ones are deleted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compress: Option<bool>, // gzip the rotated files
+}
+
+impl LogRotation {
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.max_size.is_none() && self.max_age.is_none() {
+            return Err(Error::msg("A log rotation needs a max_size or a max_age"));
+        }
+        if self.max_size == Some(0) || self.max_age == Some(0) || self.max_files == Some(0) {
+            return Err(Error::msg(
+                "The max_size, max_age and max_files of a log rotation must be greater than 0",
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn get_max_age(&self) -> Option<Duration> {
+        self.max_age.map(Duration::from_secs)
+    }
+
+    pub fn get_max_files(&self) -> u32 {
+        self.max_files.unwrap_or(DEFAULT_LOG_MAX_FILES)
+    }
+
+    pub fn get_compress(&self) -> bool {
+        self.compress.unwrap_or(false)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct Service {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub id: Option<String>,
@@ -20,7 +334,7 @@ pub struct Service {
     pub command: String,
     pub working_dir: String,
     pub description: Option<String>,
-    pub depends_on: Vec<String>,
+    pub depends_on: DependsOn,
     #[serde(skip_serializing, skip_deserializing)]
     pub dependencies: Vec<String>,
     pub env: HashMap<String, String>,
@@ -30,6 +344,108 @@ pub struct Service {
     pub port: u16,
     pub stdout: String,
     pub stderr: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_signal: Option<String>, // SIGTERM, SIGINT, SIGQUIT, ...
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_timeout: Option<u64>, // seconds before escalating to SIGKILL
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub restart: Option<RestartPolicy>, // no, on-failure, always, unless-stopped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_restarts: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backoff_seconds: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_backoff_seconds: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_seconds: Option<u64>, // seconds a service must stay up to be considered started
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub healthcheck: Option<HealthCheck>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_rotation: Option<LogRotation>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_line_length: Option<usize>, // bytes of output logged as one line, longer lines are split
+}
+
+impl Service {
+    pub fn validate(&self) -> Result<(), Error> {
+        self.get_stop_signal()?;
+        if let Some(healthcheck) = &self.healthcheck {
+            healthcheck
+                .validate()
+                .map_err(|e| Error::msg(format!("{}: {}", self.name, e)))?;
+            if healthcheck.command.is_none()
+                && healthcheck.http.is_none()
+                && healthcheck.tcp.is_none()
+                && self.port == 0
+            {
+                return Err(Error::msg(format!(
+                    "{}: a healthcheck needs one of command, http or tcp, or a port to connect to",
+                    self.name
+                )));
+            }
+        }
+        if let Some(log_rotation) = &self.log_rotation {
+            log_rotation
+                .validate()
+                .map_err(|e| Error::msg(format!("{}: {}", self.name, e)))?;
+        }
+        if self.max_line_length == Some(0) {
+            return Err(Error::msg(format!(
+                "{}: max_line_length must be greater than 0",
+                self.name
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn get_stop_signal(&self) -> Result<Signal, Error> {
+        let name = self
+            .stop_signal
+            .as_deref()
+            .unwrap_or("SIGTERM")
+            .to_uppercase();
+        let name = match name.starts_with("SIG") {
+            true => name,
+            false => format!("SIG{}", name),
+        };
+        Signal::from_str(&name).map_err(|_| Error::msg(format!("Invalid stop signal: {}", name)))
+    }
+
+    pub fn get_stop_timeout(&self) -> Duration {
+        Duration::from_secs(self.stop_timeout.unwrap_or(DEFAULT_STOP_TIMEOUT))
+    }
+
+    /// `autorestart = true` is kept as a shorthand for `restart = "always"`
+    pub fn get_restart_policy(&self) -> RestartPolicy {
+        match (&self.restart, self.autorestart) {
+            (Some(policy), _) => policy.clone(),
+            (None, true) => RestartPolicy::Always,
+            (None, false) => RestartPolicy::No,
+        }
+    }
+
+    pub fn get_max_restarts(&self) -> u32 {
+        self.max_restarts.unwrap_or(DEFAULT_MAX_RESTARTS)
+    }
+
+    /// Delay before the given restart attempt (starting at 1), doubled after each
+    /// attempt and capped to `max_backoff_seconds`
+    pub fn get_backoff(&self, attempt: u32) -> Duration {
+        let initial = self.backoff_seconds.unwrap_or(DEFAULT_BACKOFF_SECONDS);
+        let max = self
+            .max_backoff_seconds
+            .unwrap_or(DEFAULT_MAX_BACKOFF_SECONDS);
+        let delay = initial.saturating_mul(2u64.saturating_pow(attempt.saturating_sub(1)));
+        Duration::from_secs(delay.min(max))
+    }
+
+    pub fn get_start_seconds(&self) -> Duration {
+        Duration::from_secs(self.start_seconds.unwrap_or(DEFAULT_START_SECONDS))
+    }
+
+    pub fn get_max_line_length(&self) -> usize {
+        self.max_line_length.unwrap_or(DEFAULT_MAX_LINE_LENGTH)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
@@ -37,3 +453,282 @@ pub struct ConfigurationData {
     pub project: String,
     pub services: Vec<Service>,
 }
+
+impl ConfigurationData {
+    /// Fills `Service::dependencies` with the ids of the services named in `depends_on`,
+    /// the ids of the services being already assigned.
+    pub fn resolve_dependencies(&mut self) -> Result<(), Error> {
+        let services = self.services.clone();
+        for service in &mut self.services {
+            let mut dependencies = vec![];
+            for (name, dependency) in service.depends_on.dependencies() {
+                match services.iter().find(|s| s.name == name) {
+                    Some(dependency_service) => {
+                        if dependency.condition == DependencyCondition::Healthy
+                            && dependency_service.healthcheck.is_none()
+                        {
+                            return Err(Error::msg(format!(
+                                "Service {} depends on {} being healthy, but {} has no healthcheck",
+                                service.name, name, name
+                            )));
+                        }
+                        dependencies.push(dependency_service.id.clone().unwrap());
+                    }
+                    None => {
+                        return Err(Error::msg(format!("Service {} not found", name)));
+                    }
+                }
+            }
+            service.dependencies = dependencies;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HCL: &str = r#"project = "demo"
+services = [
+  {
+    name = "db"
+    type = "exec"
+    command = "postgres"
+    working_dir = "/tmp"
+    depends_on = []
+    env = {}
+    autostart = true
+    autorestart = false
+    port = 5432
+    stdout = "/tmp/db.log"
+    stderr = "/tmp/db.err"
+  },
+  {
+    name = "api"
+    type = "exec"
+    command = "npm start"
+    working_dir = "/tmp"
+    depends_on = {
+      db = { condition = "started", timeout = 30 }
+    }
+    env = { PORT = "8080" }
+    autostart = true
+    autorestart = true
+    port = 8080
+    stdout = "/tmp/api.log"
+    stderr = "/tmp/api.err"
+  }
+]
+"#;
+
+    const TOML: &str = r#"project = "demo"
+
+[[services]]
+name = "db"
+type = "exec"
+command = "postgres"
+working_dir = "/tmp"
+depends_on = []
+env = {}
+autostart = true
+autorestart = false
+port = 5432
+stdout = "/tmp/db.log"
+stderr = "/tmp/db.err"
+
+[[services]]
+name = "api"
+type = "exec"
+command = "npm start"
+working_dir = "/tmp"
+depends_on = { db = { condition = "started", timeout = 30 } }
+env = { PORT = "8080" }
+autostart = true
+autorestart = true
+port = 8080
+stdout = "/tmp/api.log"
+stderr = "/tmp/api.err"
+"#;
+
+    const JSON: &str = r#"{
+  "project": "demo",
+  "services": [
+    {
+      "name": "db",
+      "type": "exec",
+      "command": "postgres",
+      "working_dir": "/tmp",
+      "depends_on": [],
+      "env": {},
+      "autostart": true,
+      "autorestart": false,
+      "port": 5432,
+      "stdout": "/tmp/db.log",
+      "stderr": "/tmp/db.err"
+    },
+    {
+      "name": "api",
+      "type": "exec",
+      "command": "npm start",
+      "working_dir": "/tmp",
+      "depends_on": { "db": { "condition": "started", "timeout": 30 } },
+      "env": { "PORT": "8080" },
+      "autostart": true,
+      "autorestart": true,
+      "port": 8080,
+      "stdout": "/tmp/api.log",
+      "stderr": "/tmp/api.err"
+    }
+  ]
+}
+"#;
+
+    const YAML: &str = r#"project: demo
+services:
+  - name: db
+    type: exec
+    command: postgres
+    working_dir: /tmp
+    depends_on: []
+    env: {}
+    autostart: true
+    autorestart: false
+    port: 5432
+    stdout: /tmp/db.log
+    stderr: /tmp/db.err
+  - name: api
+    type: exec
+    command: npm start
+    working_dir: /tmp
+    depends_on:
+      db:
+        condition: started
+        timeout: 30
+    env:
+      PORT: "8080"
+    autostart: true
+    autorestart: true
+    port: 8080
+    stdout: /tmp/api.log
+    stderr: /tmp/api.err
+"#;
+
+    const FORMATS: [(ConfigFormat, &str); 4] = [
+        (ConfigFormat::HCL, HCL),
+        (ConfigFormat::TOML, TOML),
+        (ConfigFormat::JSON, JSON),
+        (ConfigFormat::YAML, YAML),
+    ];
+
+    fn api_depends_on() -> DependsOn {
+        DependsOn::Map(BTreeMap::from([(
+            "db".to_string(),
+            Dependency {
+                condition: DependencyCondition::Started,
+                timeout: Some(30),
+                restart: None,
+            },
+        )]))
+    }
+
+    fn assert_demo(format: ConfigFormat, config: &ConfigurationData) {
+        assert_eq!(config.project, "demo", "{}", format);
+        let names: Vec<&str> = config.services.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["db", "api"], "{}", format);
+        let db = &config.services[0];
+        assert_eq!(db.depends_on, DependsOn::List(vec![]), "{}", format);
+        assert_eq!(db.port, 5432, "{}", format);
+        assert!(!db.autorestart, "{}", format);
+        let api = &config.services[1];
+        assert_eq!(api.depends_on, api_depends_on(), "{}", format);
+        assert_eq!(
+            api.env.get("PORT").map(String::as_str),
+            Some("8080"),
+            "{}",
+            format
+        );
+        assert_eq!(api.command, "npm start", "{}", format);
+    }
+
+    #[test]
+    fn parses_every_format() {
+        for (format, content) in FORMATS {
+            let config = format.parse(content).unwrap();
+            assert_demo(format, &config);
+        }
+    }
+
+    #[test]
+    fn keeps_depends_on_as_a_list_or_a_map() {
+        for (format, content) in FORMATS {
+            let mut config = format.parse(content).unwrap();
+            config.services[0].depends_on = DependsOn::List(vec!["cache".to_string()]);
+
+            let serialized = format.serialize(&config).unwrap();
+            let parsed = format.parse(&serialized).unwrap();
+            assert_eq!(
+                parsed.services[0].depends_on,
+                DependsOn::List(vec!["cache".to_string()]),
+                "{}",
+                format
+            );
+            assert_eq!(
+                parsed.services[1].depends_on,
+                api_depends_on(),
+                "{}",
+                format
+            );
+        }
+    }
+
+    fn error(format: ConfigFormat, content: &str) -> String {
+        format.parse(content).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn reports_the_location_of_hcl_errors() {
+        let message = error(
+            ConfigFormat::HCL,
+            "project = \"demo\"\nservices = [\n  {\n    port = = 80\n  }\n]\n",
+        );
+        assert!(
+            message.starts_with("Invalid HCL Superfile at line 4, column 12: "),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn reports_the_location_of_toml_errors() {
+        let message = error(ConfigFormat::TOML, "project = \"demo\"\nservices = 3\n");
+        assert!(
+            message.starts_with("Invalid TOML Superfile at line 2, column 12: "),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn reports_the_location_of_json_errors() {
+        let message = error(
+            ConfigFormat::JSON,
+            "{\n  \"project\": \"demo\",\n  \"services\": [,]\n}\n",
+        );
+        assert!(
+            message.starts_with("Invalid JSON Superfile at line 3, column 16: "),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn reports_the_location_of_yaml_errors() {
+        let message = error(ConfigFormat::YAML, "project: demo\nservices: 3\n");
+        assert!(
+            message.starts_with("Invalid YAML Superfile at line 2, column 11: "),
+            "{}",
+            message
+        );
+    }
+}
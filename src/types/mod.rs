@@ -5,7 +5,7 @@ pub mod status;
 
 pub const UNIX_SOCKET_PATH: &str = "//tmp/superviseur.sock";
 
-pub const SUPERFILE: &str = "Superfile.hcl";
+pub const SUPERFILE: &str = "Superfile";
 
 pub const BANNER: &str = r#"
          _____                             _                     
@@ -19,6 +19,9 @@ pub enum State {
     Idle,
     Locked,
     WaitingForCpu,
+    Backoff,
+    Fatal,
+    Failed,
     Unknown,
 }
 
@@ -45,6 +48,9 @@ impl FromStr for State {
             "Idle" => Ok(State::Idle),
             "Locked" => Ok(State::Locked),
             "WaitingForCpu" => Ok(State::WaitingForCpu),
+            "Backoff" => Ok(State::Backoff),
+            "Fatal" => Ok(State::Fatal),
+            "Failed" => Ok(State::Failed),
             _ => Err(Error::msg("Unknown state")),
         }
     }
@@ -66,11 +72,47 @@ impl Display for State {
             State::Idle => write!(f, "Idle"),
             State::Locked => write!(f, "Locked"),
             State::WaitingForCpu => write!(f, "WaitingForCpu"),
+            State::Backoff => write!(f, "Backoff"),
+            State::Fatal => write!(f, "Fatal"),
+            State::Failed => write!(f, "Failed"),
             State::Unknown => write!(f, "Unknown"),
         }
     }
 }
 
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub enum Health {
+    Starting,
+    Healthy,
+    Unhealthy,
+    #[default]
+    Unknown,
+}
+
+impl FromStr for Health {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Starting" => Ok(Health::Starting),
+            "Healthy" => Ok(Health::Healthy),
+            "Unhealthy" => Ok(Health::Unhealthy),
+            "Unknown" => Ok(Health::Unknown),
+            _ => Err(Error::msg("Unknown health")),
+        }
+    }
+}
+
+impl Display for Health {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Health::Starting => write!(f, "Starting"),
+            Health::Healthy => write!(f, "Healthy"),
+            Health::Unhealthy => write!(f, "Unhealthy"),
+            Health::Unknown => write!(f, "Unknown"),
+        }
+    }
+}
+
 #[derive(Default, Tabled, Clone)]
 pub struct Process {
     #[tabled(rename = "NAME")]
@@ -83,8 +125,10 @@ pub struct Process {
     pub uid: Option<u32>,
     #[tabled(skip)]
     pub gid: Option<u32>,
-    #[tabled(skip)]
+    #[tabled(rename = "STATE")]
     pub state: State,
+    #[tabled(display_with = "display_health", rename = "HEALTH")]
+    pub health: Health,
     #[tabled(skip)]
     pub cpu: Option<f32>,
     #[tabled(skip)]
@@ -111,6 +155,18 @@ pub struct Process {
     pub stderr: String,
     #[tabled(rename = "SERVICE_ID")]
     pub service_id: String,
+    #[tabled(skip)]
+    pub exit_code: Option<i32>,
+    #[tabled(skip)]
+    pub exit_signal: Option<String>,
+    #[tabled(skip)]
+    pub exited_at: Option<DateTime<Utc>>,
+    #[tabled(skip)]
+    pub restarts: u32,
+    #[tabled(skip)]
+    pub error: Option<String>,
+    #[tabled(skip)]
+    pub stopped_by_user: bool,
 }
 
 fn display_option<T: ToString>(value: &Option<T>) -> String {
@@ -120,6 +176,13 @@ fn display_option<T: ToString>(value: &Option<T>) -> String {
     }
 }
 
+fn display_health(value: &Health) -> String {
+    match value {
+        Health::Unknown => "-".to_string(),
+        health => health.to_string(),
+    }
+}
+
 fn display_up_time(value: &Option<DateTime<Utc>>) -> String {
     match value {
         Some(v) => format!("Up {}", format_duration(Utc::now() - *v)),
@@ -2,7 +2,6 @@ use std::{
     path::Path,
     pin::Pin,
     task::{Context, Poll},
-    thread,
     time::Duration,
 };
 
@@ -21,10 +20,12 @@ impl WatchForChanges {
         service: Service,
         project: String,
     ) -> Self {
-        thread::spawn(move || {
-            let internal = WatchForChangesInternal::new(&dir, superviseur_tx, service, project);
-            futures::executor::block_on(internal);
-        });
+        match WatchForChangesInternal::new(&dir, superviseur_tx, service, project) {
+            Ok(internal) => {
+                tokio::spawn(internal);
+            }
+            Err(e) => println!("Cannot watch {}: {}", dir, e),
+        }
         Self {}
     }
 }
@@ -34,7 +35,7 @@ struct WatchForChangesInternal {
     superviseur_tx: mpsc::UnboundedSender<SuperviseurCommand>,
     service: Service,
     project: String,
-    watcher: Box<dyn Watcher>,
+    watcher: Box<dyn Watcher + Send>,
 }
 
 impl WatchForChangesInternal {
@@ -43,9 +44,9 @@ impl WatchForChangesInternal {
         superviseur_tx: mpsc::UnboundedSender<SuperviseurCommand>,
         service: Service,
         project: String,
-    ) -> Self {
+    ) -> Result<Self, Error> {
         let (cmd_tx, cmd_rx) = mpsc::unbounded_channel();
-        let mut watcher: Box<dyn Watcher> =
+        let mut watcher: Box<dyn Watcher + Send> =
             if RecommendedWatcher::kind() == WatcherKind::PollWatcher {
                 // custom config for PollWatcher kind
                 // you
@@ -56,8 +57,7 @@ impl WatchForChangesInternal {
                             cmd_tx.send(result).unwrap();
                         },
                         config,
-                    )
-                    .unwrap(),
+                    )?,
                 )
             } else {
                 // use default config for everything else
@@ -67,20 +67,17 @@ impl WatchForChangesInternal {
                             cmd_tx.send(result).unwrap();
                         },
                         Config::default(),
-                    )
-                    .unwrap(),
+                    )?,
                 )
             };
-        watcher
-            .watch(Path::new(dir), notify::RecursiveMode::Recursive)
-            .unwrap();
-        Self {
+        watcher.watch(Path::new(dir), notify::RecursiveMode::Recursive)?;
+        Ok(Self {
             cmd_rx,
             service,
             project,
             superviseur_tx,
             watcher,
-        }
+        })
     }
 }
 
@@ -105,6 +102,7 @@ impl Future for WatchForChangesInternal {
                         .send(SuperviseurCommand::Restart(
                             self.service.clone(),
                             self.project.clone(),
+                            None,
                         ))
                         .unwrap();
                 }
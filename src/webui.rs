@@ -9,10 +9,7 @@ use async_graphql::{http::GraphiQLSource, Schema};
 use async_graphql_actix_web::{GraphQLRequest, GraphQLResponse, GraphQLSubscription};
 use mime_guess::from_path;
 use rust_embed::RustEmbed;
-use std::{
-    collections::HashMap,
-    sync::{Arc, Mutex},
-};
+use std::sync::{Arc, Mutex};
 use tokio::sync::mpsc;
 
 use crate::{
@@ -20,8 +17,8 @@ use crate::{
         schema::{Mutation, Query, Subscription},
         SuperviseurSchema,
     },
-    superviseur::{ProcessEvent, Superviseur, SuperviseurCommand},
-    types::{configuration::ConfigurationData, process::Process},
+    registry::Registry,
+    superviseur::{ProcessEvent, SuperviseurCommand},
 };
 
 #[derive(RustEmbed)]
@@ -96,9 +93,7 @@ pub async fn start_webui(
     config_file_path: String,
     cmd_tx: mpsc::UnboundedSender<SuperviseurCommand>,
     event_tx: mpsc::UnboundedSender<ProcessEvent>,
-    superviseur: Superviseur,
-    processes: Arc<Mutex<Vec<(Process, String)>>>,
-    config_map: Arc<Mutex<HashMap<String, ConfigurationData>>>,
+    registry: Arc<Mutex<Registry>>,
 ) -> std::io::Result<()> {
     let addr = format!("0.0.0.0:{}", 5478);
 
@@ -108,11 +103,9 @@ pub async fn start_webui(
         Subscription::default(),
     )
     .data(config_file_path)
-    .data(superviseur)
     .data(cmd_tx)
     .data(event_tx)
-    .data(processes)
-    .data(config_map)
+    .data(registry)
     .finish();
 
     HttpServer::new(move || {